@@ -0,0 +1,107 @@
+use serde::Deserialize;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use std::fmt;
+
+use crate::error::GraynessError;
+
+//decodes CBOR-encoded arguments (e.g. from typst's `cbor.encode`), naming the offending field on failure
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, GraynessError> {
+    let mut deserializer = minicbor_serde::Deserializer::new(bytes);
+    serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        GraynessError::InvalidArgument {
+            name: (path != "." && path != "?").then_some(path),
+            message: e.inner().to_string(),
+        }
+    })
+}
+
+fn le_bytes<const N: usize>(
+    bytes: &[u8],
+    name: &str,
+    kind: &str,
+) -> Result<[u8; N], GraynessError> {
+    bytes.try_into().map_err(|_| {
+        GraynessError::invalid_argument(
+            name,
+            format!("expected {N} bytes for {kind}, got {}", bytes.len()),
+        )
+    })
+}
+
+pub fn f32_arg(bytes: &[u8], name: &str) -> Result<f32, GraynessError> {
+    Ok(f32::from_le_bytes(le_bytes(bytes, name, "a float")?))
+}
+
+pub fn u32_arg(bytes: &[u8], name: &str) -> Result<u32, GraynessError> {
+    Ok(u32::from_le_bytes(le_bytes(bytes, name, "an int")?))
+}
+
+pub fn i32_arg(bytes: &[u8], name: &str) -> Result<i32, GraynessError> {
+    Ok(i32::from_le_bytes(le_bytes(bytes, name, "an int")?))
+}
+
+pub fn u8_arg(bytes: &[u8], name: &str) -> Result<u8, GraynessError> {
+    Ok(u8::from_le_bytes(le_bytes(bytes, name, "an int")?))
+}
+
+//a keyword argument given as a UTF-8 string, e.g. the name of a filter
+pub fn str_arg<T: DeserializeOwned>(bytes: &[u8], name: &str) -> Result<T, GraynessError> {
+    let s = std::str::from_utf8(bytes)
+        .map_err(|e| GraynessError::invalid_argument(name, format!("expected a string: {e}")))?;
+    T::deserialize(de::value::StrDeserializer::<de::value::Error>::new(s))
+        .map_err(|e| GraynessError::invalid_argument(name, e.to_string()))
+}
+
+//a color as its four RGBA bytes
+pub fn rgba_arg(bytes: &[u8], name: &str) -> Result<[u8; 4], GraynessError> {
+    le_bytes(bytes, name, "an RGBA color")
+}
+
+pub fn bool_arg(bytes: &[u8]) -> bool {
+    !bytes.is_empty() && bytes[0] != 0
+}
+
+//typst encodes whole numbers as CBOR integers, so float arguments have to accept those as well
+struct Float(f32);
+
+impl<'de> Deserialize<'de> for Float {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FloatVisitor;
+
+        impl Visitor<'_> for FloatVisitor {
+            type Value = Float;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number")
+            }
+
+            //the precision loss is acceptable, all float arguments are f32 anyway
+            #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Float, E> {
+                Ok(Float(v as f32))
+            }
+
+            #[allow(clippy::cast_precision_loss)]
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Float, E> {
+                Ok(Float(v as f32))
+            }
+
+            #[allow(clippy::cast_precision_loss)]
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Float, E> {
+                Ok(Float(v as f32))
+            }
+        }
+
+        deserializer.deserialize_any(FloatVisitor)
+    }
+}
+
+pub fn float<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    Ok(Float::deserialize(deserializer)?.0)
+}
+
+pub fn float_matrix<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[f32; 20], D::Error> {
+    let matrix: [Float; 20] = Deserialize::deserialize(deserializer)?;
+    Ok(matrix.map(|v| v.0))
+}
@@ -0,0 +1,107 @@
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::error::GraynessError;
+use crate::limits::Limits;
+#[cfg(feature = "vector")]
+use crate::vector;
+use crate::{args, raster};
+
+#[cfg(target_arch = "wasm32")]
+use crate::{__BytesOrResultBytes, __send_result_to_host, __write_args_to_buffer};
+#[cfg(target_arch = "wasm32")]
+use wasm_minimal_protocol::wasm_func;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Svg,
+    Raster,
+}
+
+//raster formats are recognized by their magic bytes, SVG files by their root element
+#[cfg_attr(not(feature = "vector"), allow(unused_variables))]
+pub fn detect_kind(bytes: &[u8], limits: &Limits) -> (Kind, Option<ImageFormat>) {
+    if let Ok(format) = image::guess_format(bytes) {
+        return (Kind::Raster, Some(format));
+    }
+    //SVGZ files are decompressed first, the root element is checked the same way
+    #[cfg(feature = "vector")]
+    if vector::is_gzip(bytes) {
+        return match vector::decompress(bytes, limits) {
+            Ok(svg_bytes) => detect_kind(&svg_bytes, limits),
+            //left to the SVG pipeline, which reports the exceeded limit
+            Err(GraynessError::LimitExceeded(_)) => (Kind::Svg, None),
+            Err(_) => (Kind::Raster, None),
+        };
+    }
+
+    #[cfg(feature = "vector")]
+    let svg = vector::is_svg(bytes);
+    //without the XML parser, the root element is only looked for near the start
+    #[cfg(not(feature = "vector"))]
+    let svg = {
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+        let head = head.trim_start_matches('\u{feff}').trim_start();
+        head.starts_with('<') && head.contains("<svg")
+    };
+    if svg {
+        (Kind::Svg, None)
+    } else {
+        //left to the raster decoder, which reports the unknown format
+        (Kind::Raster, None)
+    }
+}
+
+//the limits given alongside the operations, which apply to both pipelines
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct AutoOptions {
+    limits: Limits,
+}
+
+#[derive(Serialize)]
+struct Detected {
+    kind: Kind,
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Processed {
+    kind: Kind,
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn detect(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let (kind, format) = detect_kind(image_bytes, &Limits::default());
+    let format = match kind {
+        #[cfg(feature = "vector")]
+        Kind::Svg if vector::is_gzip(image_bytes) => Some("svgz".to_string()),
+        Kind::Svg => Some("svg".to_string()),
+        Kind::Raster => format.map(|f| format!("{f:?}").to_lowercase()),
+    };
+    minicbor_serde::to_vec(Detected { kind, format })
+        .map_err(|e| GraynessError::EncodeFailed(format!("Could not encode image kind: {e}")))
+}
+
+//runs `pipeline_cbor` or `svg_pipeline_cbor` depending on the kind of image
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn auto_pipeline(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let options: AutoOptions = args::decode(args_bytes)?;
+    let (kind, _) = detect_kind(image_bytes, &options.limits);
+    let data = match kind {
+        #[cfg(feature = "vector")]
+        Kind::Svg => vector::svg_pipeline_cbor(image_bytes, args_bytes)?,
+        #[cfg(not(feature = "vector"))]
+        Kind::Svg => {
+            return Err(GraynessError::UnsupportedFormat(
+                "SVG support is not compiled into this build of the plugin".to_string(),
+            ));
+        }
+        Kind::Raster => raster::pipeline_cbor(image_bytes, args_bytes)?,
+    };
+    minicbor_serde::to_vec(Processed { kind, data })
+        .map_err(|e| GraynessError::EncodeFailed(format!("Could not encode processed image: {e}")))
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+//the error of every plugin function. its message starts with a stable code in brackets, e.g. `[invalid-argument]`,
+//so typst wrappers can tell failures apart without depending on the wording of the message
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraynessError {
+    //a malformed or out of range argument or option, without a name if the whole CBOR dictionary is malformed
+    InvalidArgument {
+        name: Option<String>,
+        message: String,
+    },
+    //an unknown image format, or one that is not compiled in or can't be written
+    UnsupportedFormat(String),
+    DecodeFailed(String),
+    EncodeFailed(String),
+    //malformed XML, at a 1-based position
+    SvgParse {
+        line: u64,
+        col: u64,
+        message: String,
+    },
+    //well-formed XML which can't be processed, e.g. an SVG without a size
+    InvalidSvg(String),
+    //an image exceeding the resource limits, see `Limits`
+    LimitExceeded(String),
+}
+
+impl GraynessError {
+    pub fn invalid_argument(name: &str, message: impl Into<String>) -> Self {
+        GraynessError::InvalidArgument {
+            name: Some(name.to_string()),
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            GraynessError::InvalidArgument { .. } => "invalid-argument",
+            GraynessError::UnsupportedFormat(_) => "unsupported-format",
+            GraynessError::DecodeFailed(_) => "decode-failed",
+            GraynessError::EncodeFailed(_) => "encode-failed",
+            GraynessError::SvgParse { .. } => "svg-parse",
+            GraynessError::InvalidSvg(_) => "invalid-svg",
+            GraynessError::LimitExceeded(_) => "limit-exceeded",
+        }
+    }
+}
+
+impl fmt::Display for GraynessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] ", self.code())?;
+        match self {
+            GraynessError::InvalidArgument {
+                name: Some(name),
+                message,
+            } => write!(f, "Invalid argument `{name}`: {message}"),
+            GraynessError::InvalidArgument {
+                name: None,
+                message,
+            } => write!(f, "Could not decode arguments: {message}"),
+            GraynessError::SvgParse { line, col, message } => write!(
+                f,
+                "Could not parse SVG data at line {line}, column {col}: {message}"
+            ),
+            GraynessError::UnsupportedFormat(message)
+            | GraynessError::DecodeFailed(message)
+            | GraynessError::EncodeFailed(message)
+            | GraynessError::InvalidSvg(message)
+            | GraynessError::LimitExceeded(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for GraynessError {}
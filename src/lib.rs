@@ -1,6 +1,16 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_minimal_protocol::initiate_protocol;
 
+mod args;
+pub mod detect;
+pub mod error;
+pub mod limits;
 pub mod raster;
+#[cfg(feature = "vector")]
 pub mod vector;
 
+//the plugin functions are marked with `wasm_func`, which only exports them to typst together with their
+//byte shims when building the plugin, natively they are plain functions. The functions named `*_cbor`
+//take their arguments as a single CBOR-encoded dictionary, which may also contain the options for the whole image
+#[cfg(target_arch = "wasm32")]
 initiate_protocol!();
@@ -0,0 +1,105 @@
+use image::error::{ImageError, LimitErrorKind};
+use serde::Deserialize;
+
+use crate::error::GraynessError;
+
+//resource limits protecting typst from images taking too much memory or time, e.g. a tiny PNG declaring a huge size,
+//a decompression bomb or deeply nested SVG elements. given as the `limits` dictionary of the options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    //largest size of a decoded or created image in pixels
+    pub max_width: u32,
+    pub max_height: u32,
+    //memory a decoded image or a decompressed SVGZ file may take, in MiB
+    pub max_memory: u32,
+    //how deeply SVG elements may be nested, and how many there may be in total.
+    //the SVG parsers recurse into nested elements, and typst only gives plugins a small call stack
+    pub max_depth: usize,
+    pub max_elements: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_width: 16384,
+            max_height: 16384,
+            max_memory: 256,
+            max_depth: 128,
+            max_elements: 1_000_000,
+        }
+    }
+}
+
+impl Limits {
+    pub fn max_bytes(&self) -> u64 {
+        u64::from(self.max_memory) * 1024 * 1024
+    }
+
+    //the limits enforced by the decoders of the image crate
+    pub fn image(&self) -> image::Limits {
+        let mut limits = image::Limits::default();
+        limits.max_image_width = Some(self.max_width);
+        limits.max_image_height = Some(self.max_height);
+        limits.max_alloc = Some(self.max_bytes());
+        limits
+    }
+
+    //checks the size of an image before creating it, assuming 8-bit RGBA pixels
+    pub fn check_size(&self, width: u32, height: u32) -> Result<(), GraynessError> {
+        self.check_image(width, height, u64::from(width) * u64::from(height) * 4)
+    }
+
+    pub fn check_image(&self, width: u32, height: u32, bytes: u64) -> Result<(), GraynessError> {
+        if width > self.max_width || height > self.max_height {
+            return Err(GraynessError::LimitExceeded(format!(
+                "An image of {width}x{height} pixels exceeds the limit of {}x{} pixels, see the `max_width` and `max_height` limits",
+                self.max_width, self.max_height
+            )));
+        }
+        if bytes > self.max_bytes() {
+            return Err(GraynessError::LimitExceeded(format!(
+                "An image of {width}x{height} pixels exceeds the memory limit of {} MiB, see the `max_memory` limit",
+                self.max_memory
+            )));
+        }
+        Ok(())
+    }
+
+    //checks an SVG element at the given nesting depth, counting it towards the total
+    pub fn check_element(&self, depth: usize, elements: &mut usize) -> Result<(), GraynessError> {
+        *elements += 1;
+        if depth > self.max_depth {
+            return Err(GraynessError::LimitExceeded(format!(
+                "The SVG exceeds the limit of {} nested elements, see the `max_depth` limit",
+                self.max_depth
+            )));
+        }
+        if *elements > self.max_elements {
+            return Err(GraynessError::LimitExceeded(format!(
+                "The SVG exceeds the limit of {} elements, see the `max_elements` limit",
+                self.max_elements
+            )));
+        }
+        Ok(())
+    }
+
+    //the decoders only report which kind of limit was hit, not the size of the image
+    pub fn decode_error(&self, e: ImageError) -> GraynessError {
+        match e {
+            ImageError::Limits(e) => match e.kind() {
+                LimitErrorKind::DimensionError => GraynessError::LimitExceeded(format!(
+                    "The image exceeds the limit of {}x{} pixels, see the `max_width` and `max_height` limits",
+                    self.max_width, self.max_height
+                )),
+                LimitErrorKind::InsufficientMemory => GraynessError::LimitExceeded(format!(
+                    "Decoding the image exceeds the memory limit of {} MiB, see the `max_memory` limit",
+                    self.max_memory
+                )),
+                _ => GraynessError::DecodeFailed(format!("Could not decode image data: {e}")),
+            },
+            ImageError::Unsupported(e) => GraynessError::UnsupportedFormat(e.to_string()),
+            e => GraynessError::DecodeFailed(format!("Could not decode image data: {e}")),
+        }
+    }
+}
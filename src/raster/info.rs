@@ -0,0 +1,307 @@
+use image::{ImageDecoder, ImageFormat, ImageReader};
+use serde::Serialize;
+use std::io::Cursor;
+
+use super::unknown_format;
+use crate::error::GraynessError;
+
+#[derive(Debug, Serialize)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    //the color type as stored in the file, e.g. `cmyk8` or `indexed4`, which the decoders may convert
+    pub color_type: String,
+    //bits per channel as stored in the file, which may be less than in the decoded image
+    pub bit_depth: u16,
+    pub has_alpha: bool,
+    pub frame_count: u32,
+    //horizontal and vertical resolution in dots per inch, if the file specifies it
+    pub dpi: Option<(f32, f32)>,
+    //the EXIF orientation value, 1 meaning no transformation is necessary. None if it can't be read
+    pub orientation: Option<u8>,
+}
+
+//reads all infos from the image headers, without decoding the pixel data
+pub fn read_info(bytes: &[u8]) -> Result<ImageInfo, GraynessError> {
+    let img_r = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| GraynessError::DecodeFailed(format!("Could not read image data: {e}")))?;
+    let format = img_r.format().ok_or(unknown_format())?;
+    if !format.reading_enabled() {
+        return Err(super::not_compiled_in(format));
+    }
+    let mut decoder = img_r
+        .into_decoder()
+        .map_err(|e| GraynessError::DecodeFailed(format!("Could not read image header: {e}")))?;
+
+    let (width, height) = decoder.dimensions();
+    let color = decoder.color_type();
+    let original_color = decoder.original_color_type();
+    //broken metadata shouldn't prevent reading the other infos
+    let orientation = decoder.orientation().ok();
+    let exif = decoder.exif_metadata().ok().flatten();
+
+    //the decoders of some formats only report the color type they convert to
+    let (color_type, bit_depth) = source_color(bytes, format).unwrap_or_else(|| {
+        (
+            format!("{original_color:?}").to_lowercase(),
+            original_color.bits_per_pixel() / u16::from(original_color.channel_count()),
+        )
+    });
+
+    Ok(ImageInfo {
+        width,
+        height,
+        format: format!("{format:?}").to_lowercase(),
+        color_type,
+        bit_depth,
+        //palettes are only known to have transparent entries after being expanded
+        has_alpha: color.has_alpha(),
+        frame_count: frame_count(bytes, format).unwrap_or(1),
+        dpi: dpi(bytes, format, exif.as_deref()),
+        orientation: orientation.map(|o| o.to_exif()),
+    })
+}
+
+//counts the frames of animated images by walking their container structure, None if it is malformed
+fn frame_count(bytes: &[u8], format: ImageFormat) -> Option<u32> {
+    match format {
+        ImageFormat::Gif => gif_frame_count(bytes),
+        ImageFormat::Png => apng_frame_count(bytes),
+        ImageFormat::WebP => webp_frame_count(bytes),
+        _ => Some(1),
+    }
+}
+
+//counts the image descriptors, see https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+fn gif_frame_count(bytes: &[u8]) -> Option<u32> {
+    //skips a sequence of data sub-blocks, returning the position after the terminator
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let len = usize::from(*bytes.get(pos)?);
+            pos += 1 + len;
+            if len == 0 {
+                return Some(pos);
+            }
+        }
+    };
+    //size of a color table, if the flags indicate one is present
+    let color_table_len = |flags: u8| {
+        if flags & 0x80 == 0 {
+            0
+        } else {
+            3 * (2 << (flags & 0x07))
+        }
+    };
+
+    let mut pos = 13 + color_table_len(*bytes.get(10)?); //header and logical screen descriptor
+    let mut frames = 0;
+    loop {
+        match *bytes.get(pos)? {
+            //extension: introducer, label and sub-blocks
+            0x21 => pos = skip_sub_blocks(pos + 2)?,
+            //image descriptor, optional local color table, LZW code size and sub-blocks
+            0x2C => {
+                frames += 1;
+                pos += 10 + color_table_len(*bytes.get(pos + 9)?);
+                pos = skip_sub_blocks(pos + 1)?;
+            }
+            0x3B => return Some(frames),
+            _ => return None,
+        }
+    }
+}
+
+//the number of frames from the acTL chunk, see https://www.w3.org/TR/png/#acTL-chunk
+fn apng_frame_count(bytes: &[u8]) -> Option<u32> {
+    let mut pos = 8; //skip the signature
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let data = bytes.get(pos + 8..(pos + 8).checked_add(len)?)?;
+        match kind {
+            b"acTL" if len == 8 => return Some(u32::from_be_bytes(data[0..4].try_into().ok()?)),
+            //the animation control chunk must precede the image data
+            b"IDAT" | b"IEND" => return Some(1),
+            _ => pos += 12 + len, //length, type, data and crc
+        }
+    }
+    None
+}
+
+//counts the ANMF chunks, see https://developers.google.com/speed/webp/docs/riff_container
+fn webp_frame_count(bytes: &[u8]) -> Option<u32> {
+    let mut pos = 12; //RIFF header
+    let mut frames = 0;
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        if &bytes[pos..pos + 4] == b"ANMF" {
+            frames += 1;
+        }
+        //chunks are padded to an even size
+        pos = (pos + 8).checked_add(len)?.checked_add(len & 1)?;
+    }
+    Some(frames.max(1))
+}
+
+fn source_color(bytes: &[u8], format: ImageFormat) -> Option<(String, u16)> {
+    match format {
+        ImageFormat::Png => png_color(bytes),
+        ImageFormat::Jpeg => jpeg_color(bytes),
+        _ => None,
+    }
+}
+
+//the IHDR chunk, which directly follows the signature, see https://www.w3.org/TR/png/#11IHDR
+fn png_color(bytes: &[u8]) -> Option<(String, u16)> {
+    let ihdr = bytes.get(12..26)?; //type, width, height, bit depth and color type
+    if &ihdr[0..4] != b"IHDR" {
+        return None;
+    }
+    let depth = ihdr[12];
+    let kind = match ihdr[13] {
+        0 => "l",
+        2 => "rgb",
+        3 => "indexed",
+        4 => "la",
+        6 => "rgba",
+        _ => return None,
+    };
+    Some((format!("{kind}{depth}"), u16::from(depth)))
+}
+
+//the start of frame segment, see https://www.w3.org/Graphics/JPEG/itu-t81.pdf section B.2.2
+fn jpeg_color(bytes: &[u8]) -> Option<(String, u16)> {
+    let mut pos = 2; //skip the SOI marker
+    loop {
+        let marker = bytes.get(pos..pos + 4)?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+        let len = usize::from(u16::from_be_bytes([marker[2], marker[3]]));
+        match marker[1] {
+            //SOF0 to SOF15, except for DHT, JPG and DAC which share the range
+            0xC0..=0xCF if !matches!(marker[1], 0xC4 | 0xC8 | 0xCC) => {
+                let precision = *bytes.get(pos + 4)?;
+                let kind = match *bytes.get(pos + 9)? {
+                    1 => "l",
+                    3 => "rgb",
+                    4 => "cmyk",
+                    _ => return None,
+                };
+                return Some((format!("{kind}{precision}"), u16::from(precision)));
+            }
+            //the image data starts without a frame header
+            0xDA => return None,
+            _ => pos += 2 + len, //marker and segment
+        }
+    }
+}
+
+fn dpi(bytes: &[u8], format: ImageFormat, exif: Option<&[u8]>) -> Option<(f32, f32)> {
+    let from_header = match format {
+        ImageFormat::Png => png_dpi(bytes),
+        ImageFormat::Jpeg => jfif_dpi(bytes),
+        //TIFF files share their structure with EXIF chunks
+        ImageFormat::Tiff => exif_dpi(bytes),
+        _ => None,
+    };
+    from_header.or_else(|| exif.and_then(exif_dpi))
+}
+
+//the pHYs chunk, see https://www.w3.org/TR/png/#11pHYs
+fn png_dpi(bytes: &[u8]) -> Option<(f32, f32)> {
+    let mut pos = 8; //skip the signature
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let data = bytes.get(pos + 8..(pos + 8).checked_add(len)?)?;
+        match kind {
+            b"pHYs" if len == 9 => {
+                //only meters are a meaningful unit, otherwise just the aspect ratio is given
+                if data[8] != 1 {
+                    return None;
+                }
+                let x = u32::from_be_bytes(data[0..4].try_into().ok()?);
+                let y = u32::from_be_bytes(data[4..8].try_into().ok()?);
+                #[allow(clippy::cast_precision_loss)]
+                return Some((x as f32 * 0.0254, y as f32 * 0.0254));
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => pos += 12 + len, //length, type, data and crc
+        }
+    }
+    None
+}
+
+//the density fields of the JFIF APP0 segment, see https://www.w3.org/Graphics/JPEG/jfif3.pdf
+fn jfif_dpi(bytes: &[u8]) -> Option<(f32, f32)> {
+    let app0 = bytes.get(2..20)?;
+    if app0[0..2] != [0xFF, 0xE0] || &app0[4..9] != b"JFIF\0" {
+        return None;
+    }
+    let x = f32::from(u16::from_be_bytes([app0[12], app0[13]]));
+    let y = f32::from(u16::from_be_bytes([app0[14], app0[15]]));
+    match app0[11] {
+        1 => Some((x, y)),
+        2 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
+
+//the resolution tags of the first IFD of a TIFF structure, as used by EXIF chunks and TIFF files
+fn exif_dpi(chunk: &[u8]) -> Option<(f32, f32)> {
+    let little_endian = match chunk.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| -> Option<u16> {
+        let b = chunk.get(pos..pos.checked_add(2)?)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let u32_at = |pos: usize| -> Option<u32> {
+        let b = chunk.get(pos..pos.checked_add(4)?)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+    #[allow(clippy::cast_precision_loss)]
+    let rational_at = |pos: usize| -> Option<f32> {
+        let offset = u32_at(pos)? as usize;
+        let numerator = u32_at(offset)?;
+        let denominator = u32_at(offset.checked_add(4)?)?;
+        if denominator == 0 {
+            return None;
+        }
+        Some(numerator as f32 / denominator as f32)
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let mut x = None;
+    let mut y = None;
+    let mut unit = 2; //inches are the default
+    for i in 0..usize::from(u16_at(ifd)?) {
+        let entry = ifd + 2 + i * 12;
+        match u16_at(entry)? {
+            0x011A => x = rational_at(entry + 8),
+            0x011B => y = rational_at(entry + 8),
+            0x0128 => unit = u16_at(entry + 8)?,
+            _ => {}
+        }
+    }
+
+    let (x, y) = (x?, y?);
+    match unit {
+        2 => Some((x, y)),
+        3 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
@@ -1,218 +1,271 @@
-use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader, Pixel, RgbaImage};
+#[cfg(feature = "jpeg")]
+use image::codecs::jpeg::JpegEncoder;
+#[cfg(feature = "png")]
+use image::codecs::png::PngEncoder;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
 use std::io::Cursor;
+
+pub mod info;
+pub mod ops;
+pub mod options;
+pub mod pipeline;
+pub mod trace;
+
+use crate::args;
+use crate::error::GraynessError;
+use crate::limits::Limits;
+use options::{Options, OutputFormat};
+use pipeline::{
+    AmountArgs, BlurArgs, CropArgs, FitToDpiArgs, MaskArgs, MatrixArgs, Operation, ResizeArgs,
+    RotateArgs, TransparencyArgs,
+};
+
+#[cfg(target_arch = "wasm32")]
+use crate::{__BytesOrResultBytes, __send_result_to_host, __write_args_to_buffer};
+#[cfg(target_arch = "wasm32")]
 use wasm_minimal_protocol::wasm_func;
 
-use crate::__BytesOrResultBytes;
-use crate::__send_result_to_host;
-use crate::__write_args_to_buffer;
+//converts the image to a color type the encoder supports, e.g. an HDR image for PNG or a grayscale image for GIF.
+//JPEG is left alone, since its encoder converts the image itself
+fn compatible_color(img: &DynamicImage, format: ImageFormat) -> Cow<'_, DynamicImage> {
+    use DynamicImage::*;
+    match (format, img) {
+        (ImageFormat::Png, ImageRgb32F(_)) => Cow::Owned(img.to_rgb16().into()),
+        (ImageFormat::Png, ImageRgba32F(_)) => Cow::Owned(img.to_rgba16().into()),
+        (ImageFormat::Gif, ImageRgb8(_) | ImageRgba8(_)) => Cow::Borrowed(img),
+        (ImageFormat::WebP, ImageLuma8(_) | ImageLumaA8(_) | ImageRgb8(_) | ImageRgba8(_)) => {
+            Cow::Borrowed(img)
+        }
+        (ImageFormat::Gif | ImageFormat::WebP, _) => Cow::Owned(img.to_rgba8().into()),
+        _ => Cow::Borrowed(img),
+    }
+}
 
-fn write_image_buffer(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
-    let targetformat = match format {
-        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP => format,
+//the codecs are cargo features, so the plugin may recognize a format without being able to read or write it
+pub fn not_compiled_in(format: ImageFormat) -> GraynessError {
+    GraynessError::UnsupportedFormat(format!(
+        "The {} format is not compiled into this build of the plugin",
+        format!("{format:?}").to_lowercase()
+    ))
+}
+
+pub fn unknown_format() -> GraynessError {
+    GraynessError::UnsupportedFormat("Unknown image format".to_string())
+}
+
+pub fn write_image_buffer(
+    img: &DynamicImage,
+    format: ImageFormat,
+    options: &Options,
+) -> Result<Vec<u8>, GraynessError> {
+    //an explicitly chosen format is always used, otherwise only the formats typst can display are kept
+    let targetformat = match (options.format, format) {
+        (Some(OutputFormat(explicit)), _) => explicit,
+        (None, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP) => {
+            format
+        }
         _ => ImageFormat::Png,
     };
 
+    if !targetformat.writing_enabled() {
+        return Err(not_compiled_in(targetformat));
+    }
+
+    let img = compatible_color(img, targetformat);
     let mut bytes: Vec<u8> = Vec::new();
-    img.write_to(&mut Cursor::new(&mut bytes), targetformat)
-        .map_err(|e| format!("Could not write image bytes to buffer: {e:?}"))?;
+    let mut cursor = Cursor::new(&mut bytes);
+    let res = match targetformat {
+        #[cfg(feature = "jpeg")]
+        ImageFormat::Jpeg => {
+            if !(1..=100).contains(&options.quality) {
+                return Err(GraynessError::invalid_argument(
+                    "quality",
+                    format!("expected a value from 1 to 100, got {}", options.quality),
+                ));
+            }
+            img.write_with_encoder(JpegEncoder::new_with_quality(cursor, options.quality))
+        }
+        #[cfg(feature = "png")]
+        ImageFormat::Png => img.write_with_encoder(PngEncoder::new_with_quality(
+            cursor,
+            options.compression.into(),
+            options.png_filter.into(),
+        )),
+        ImageFormat::WebP if !options.lossless => {
+            return Err(GraynessError::UnsupportedFormat(
+                "Lossy WebP encoding is not supported".to_string(),
+            ));
+        }
+        _ => img.write_to(&mut cursor, targetformat),
+    };
+    res.map_err(|e| GraynessError::EncodeFailed(format!("Could not encode the image: {e}")))?;
 
     Ok(bytes)
 }
 
-fn get_decoded_image_from_bytes(bytes: &[u8]) -> Result<(DynamicImage, ImageFormat), String> {
-    let img_r = ImageReader::new(Cursor::new(bytes))
+pub fn get_decoded_image_from_bytes(
+    bytes: &[u8],
+    auto_orient: bool,
+    limits: &Limits,
+) -> Result<(DynamicImage, ImageFormat), GraynessError> {
+    let mut img_r = ImageReader::new(Cursor::new(bytes))
         .with_guessed_format()
-        .map_err(|e| format!("Guessing the image format failed: {e:?}"))?;
-    let format = img_r.format().ok_or("Unknown image format".to_string())?;
-    let decoded = img_r
-        .decode()
-        .map_err(|e| format!("Could not decode image data: {e:?}"))?;
+        .map_err(|e| GraynessError::DecodeFailed(format!("Could not read image data: {e}")))?;
+    let format = img_r.format().ok_or(unknown_format())?;
+    if !format.reading_enabled() {
+        return Err(not_compiled_in(format));
+    }
+    img_r.limits(limits.image());
+    let mut decoder = img_r.into_decoder().map_err(|e| limits.decode_error(e))?;
+    //not every decoder enforces the limits itself, so the size from the header is checked before allocating the image
+    let (width, height) = decoder.dimensions();
+    limits.check_image(width, height, decoder.total_bytes())?;
+    //a malformed EXIF chunk shouldn't make the whole image unusable, so it is treated as missing
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut decoded = DynamicImage::from_decoder(decoder).map_err(|e| limits.decode_error(e))?;
+    if auto_orient {
+        decoded.apply_orientation(orientation);
+    }
     Ok((decoded, format))
 }
 
-#[wasm_func]
-pub fn grayscale(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    let res = img.grayscale();
-    write_image_buffer(&res, format)
+pub fn apply_operations(
+    image_bytes: &[u8],
+    operations: &[Operation],
+    options: &Options,
+) -> Result<Vec<u8>, GraynessError> {
+    let (mut img, mut format) =
+        get_decoded_image_from_bytes(image_bytes, options.auto_orient, &options.limits)?;
+
+    for op in operations {
+        img = op.apply(img, options)?;
+        if op.needs_alpha() {
+            format = ImageFormat::Png; //Always use PNG for its alpha channel, unless another format was chosen explicitly
+        }
+    }
+
+    write_image_buffer(&img, format, options)
 }
 
-#[wasm_func]
-pub fn convert(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    write_image_buffer(&img, format)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn grayscale(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Grayscale], &Options::default())
 }
 
-#[wasm_func]
-pub fn decode(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, _) = get_decoded_image_from_bytes(image_bytes)?;
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn convert(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn convert_to(image_bytes: &[u8], format: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let options = Options {
+        format: Some(args::str_arg(format, "format")?),
+        ..Options::default()
+    };
+    apply_operations(image_bytes, &[], &options)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn auto_orient(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let options = Options {
+        auto_orient: true,
+        ..Options::default()
+    };
+    apply_operations(image_bytes, &[], &options)
+}
+
+//the raw RGBA pixels of the decoded image
+pub fn decode_image(image_bytes: &[u8], options: &Options) -> Result<Vec<u8>, GraynessError> {
+    let (img, _) = get_decoded_image_from_bytes(image_bytes, options.auto_orient, &options.limits)?;
     let res = img.to_rgba8();
     Ok(res.to_vec())
 }
 
-#[wasm_func]
-pub fn infos(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    let (w, h) = img.dimensions();
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn decode(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    decode_image(image_bytes, &Options::default())
+}
 
-    Ok([
-        w.to_le_bytes().as_slice(),
-        h.to_le_bytes().as_slice(),
-        format!("{format:?}").as_bytes(),
-    ]
-    .concat())
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn infos(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let res = info::read_info(image_bytes)?;
+    minicbor_serde::to_vec(&res)
+        .map_err(|e| GraynessError::EncodeFailed(format!("Could not encode image infos: {e}")))
 }
 
-#[wasm_func]
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
 pub fn mask(
     target_image_bytes: &[u8],
-    mask_image_bytges: &[u8],
+    mask_image_bytes: &[u8],
     use_alpha: &[u8],
-) -> Result<Vec<u8>, String> {
-    let use_alpha = !use_alpha.is_empty() && use_alpha[0] != 0;
-    let (targetimg, _) = get_decoded_image_from_bytes(target_image_bytes)?;
-    let (mut mask, _) = get_decoded_image_from_bytes(mask_image_bytges)?;
-
-    let (target_width, target_height) = targetimg.dimensions();
-    if mask.dimensions() != targetimg.dimensions() {
-        mask = mask.resize_exact(
-            target_width,
-            target_height,
-            image::imageops::FilterType::Nearest,
-        );
-    }
-
-    let mut output = RgbaImage::new(target_width, target_height);
-
-    for y in 0..target_height {
-        for x in 0..target_width {
-            let mut pixel = targetimg.get_pixel(x, y);
-            let mask_pixel = mask.get_pixel(x, y);
-            let target_alpha = f32::from(pixel[3]) / 255.0;
-            let mask_alpha = if use_alpha {
-                f32::from(mask_pixel[3]) / 255.0
-            } else {
-                f32::from(mask_pixel.to_luma()[0]) / 255.0
-            };
-
-            //pixel values are always positive, no precision is lost since the floats were only intermediate anyway.
-            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-            let new_alpha = (target_alpha * mask_alpha * 255.0).round() as u8;
-            pixel[3] = new_alpha;
-            output.put_pixel(x, y, pixel);
-        }
-    }
-
-    let mut bytes: Vec<u8> = Vec::new();
-    output
-        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png) //Always use PNG for its alpha channel
-        .map_err(|e| format!("Could not write image bytes to buffer: {e:?}"))?;
-
-    Ok(bytes)
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Mask(MaskArgs {
+        mask: mask_image_bytes.to_vec(),
+        use_alpha: args::bool_arg(use_alpha),
+    });
+    apply_operations(target_image_bytes, &[op], &Options::default())
 }
 
-#[wasm_func]
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
 pub fn crop(
     image_bytes: &[u8],
     start_x: &[u8],
     start_y: &[u8],
     width: &[u8],
     height: &[u8],
-) -> Result<Vec<u8>, String> {
-    let start_x = u32::from_le_bytes(
-        start_x
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let start_y = u32::from_le_bytes(
-        start_y
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let width = u32::from_le_bytes(
-        width
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let height = u32::from_le_bytes(
-        height
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let (mut img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    let res = img.crop(start_x, start_y, width, height);
-
-    write_image_buffer(&res, format)
-}
-
-#[wasm_func]
-pub fn blur(image_bytes: &[u8], sigma: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    let sigma = f32::from_le_bytes(
-        sigma
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let res = img.blur(sigma);
-    write_image_buffer(&res, format)
-}
-
-#[wasm_func]
-pub fn transparency(image_bytes: &[u8], alpha: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, _) = get_decoded_image_from_bytes(image_bytes)?;
-    let alpha = u8::from_le_bytes(
-        alpha
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let mut res = img.to_rgba8();
-
-    for y in 0..res.height() {
-        for x in 0..res.width() {
-            let pixel = res.get_pixel_mut(x, y);
-            pixel.apply_with_alpha(|ch| ch, |_| alpha);
-        }
-    }
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Crop(CropArgs {
+        x: args::u32_arg(start_x, "start_x")?,
+        y: args::u32_arg(start_y, "start_y")?,
+        width: args::u32_arg(width, "width")?,
+        height: args::u32_arg(height, "height")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    let mut bytes: Vec<u8> = Vec::new();
-    res.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png) //Always use PNG for its alpha channel
-        .map_err(|e| format!("Could not write image bytes to buffer: {e:?}"))?;
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn blur(image_bytes: &[u8], sigma: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Blur(BlurArgs {
+        sigma: args::f32_arg(sigma, "sigma")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    Ok(bytes)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn transparency(image_bytes: &[u8], alpha: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Transparency(TransparencyArgs {
+        alpha: args::u8_arg(alpha, "alpha")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
 }
 
-#[wasm_func]
-pub fn invert(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let (mut img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    img.invert();
-    write_image_buffer(&img, format)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn invert(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Invert], &Options::default())
 }
 
-#[wasm_func]
-pub fn brighten(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    let amount = i32::from_le_bytes(
-        amount
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let res = img.brighten(amount);
-    write_image_buffer(&res, format)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn brighten(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Brighten(AmountArgs {
+        amount: args::i32_arg(amount, "amount")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
 }
 
-#[wasm_func]
-pub fn huerotate(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, String> {
-    let (img, format) = get_decoded_image_from_bytes(image_bytes)?;
-    let amount = i32::from_le_bytes(
-        amount
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to int: {e:?}"))?,
-    );
-    let res = img.huerotate(amount);
-    write_image_buffer(&res, format)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn huerotate(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Huerotate(AmountArgs {
+        amount: args::i32_arg(amount, "amount")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
 }
 
-#[wasm_func]
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
 #[allow(clippy::too_many_arguments)]
 pub fn matrix(
     image_bytes: &[u8],
@@ -236,113 +289,195 @@ pub fn matrix(
     m32: &[u8],
     m33: &[u8],
     m34: &[u8],
-) -> Result<Vec<u8>, String> {
-    let (img, _format) = get_decoded_image_from_bytes(image_bytes)?;
-    let mut res = img.to_rgba8();
-
-    let m00 = f32::from_le_bytes(
-        m00.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m01 = f32::from_le_bytes(
-        m01.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m02 = f32::from_le_bytes(
-        m02.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m03 = f32::from_le_bytes(
-        m03.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m04 = f32::from_le_bytes(
-        m04.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m10 = f32::from_le_bytes(
-        m10.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m11 = f32::from_le_bytes(
-        m11.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m12 = f32::from_le_bytes(
-        m12.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m13 = f32::from_le_bytes(
-        m13.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m14 = f32::from_le_bytes(
-        m14.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m20 = f32::from_le_bytes(
-        m20.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m21 = f32::from_le_bytes(
-        m21.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m22 = f32::from_le_bytes(
-        m22.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m23 = f32::from_le_bytes(
-        m23.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m24 = f32::from_le_bytes(
-        m24.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m30 = f32::from_le_bytes(
-        m30.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m31 = f32::from_le_bytes(
-        m31.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m32 = f32::from_le_bytes(
-        m32.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m33 = f32::from_le_bytes(
-        m33.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m34 = f32::from_le_bytes(
-        m34.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-
-    for y in 0..res.height() {
-        for x in 0..res.width() {
-            let pixel = res.get_pixel_mut(x, y);
-            let r = f32::from(pixel[0]);
-            let g = f32::from(pixel[1]);
-            let b = f32::from(pixel[2]);
-            let a = f32::from(pixel[3]);
-
-            let nr = m00 * r + m01 * g + m02 * b + m03 * a + m04 * 255.0;
-            let ng = m10 * r + m11 * g + m12 * b + m13 * a + m14 * 255.0;
-
-            let nb = m20 * r + m21 * g + m22 * b + m23 * a + m24 * 255.0;
-            let na = m30 * r + m31 * g + m32 * b + m33 * a + m34 * 255.0;
-
-            pixel[0] = nr.clamp(0.0, 255.0) as u8;
-            pixel[1] = ng.clamp(0.0, 255.0) as u8;
-            pixel[2] = nb.clamp(0.0, 255.0) as u8;
-            pixel[3] = na.clamp(0.0, 255.0) as u8;
-        }
-    }
-    let mut bytes: Vec<u8> = Vec::new();
-    res.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png) //Always use PNG for its alpha channel
-        .map_err(|e| format!("Could not write image bytes to buffer: {e:?}"))?;
-    Ok(bytes)
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Matrix(MatrixArgs {
+        matrix: [
+            args::f32_arg(m00, "m00")?,
+            args::f32_arg(m01, "m01")?,
+            args::f32_arg(m02, "m02")?,
+            args::f32_arg(m03, "m03")?,
+            args::f32_arg(m04, "m04")?,
+            args::f32_arg(m10, "m10")?,
+            args::f32_arg(m11, "m11")?,
+            args::f32_arg(m12, "m12")?,
+            args::f32_arg(m13, "m13")?,
+            args::f32_arg(m14, "m14")?,
+            args::f32_arg(m20, "m20")?,
+            args::f32_arg(m21, "m21")?,
+            args::f32_arg(m22, "m22")?,
+            args::f32_arg(m23, "m23")?,
+            args::f32_arg(m24, "m24")?,
+            args::f32_arg(m30, "m30")?,
+            args::f32_arg(m31, "m31")?,
+            args::f32_arg(m32, "m32")?,
+            args::f32_arg(m33, "m33")?,
+            args::f32_arg(m34, "m34")?,
+        ],
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn resize(
+    image_bytes: &[u8],
+    width: &[u8],
+    height: &[u8],
+    filter: &[u8],
+    mode: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Resize(ResizeArgs {
+        width: args::u32_arg(width, "width")?,
+        height: args::u32_arg(height, "height")?,
+        filter: args::str_arg(filter, "filter")?,
+        mode: args::str_arg(mode, "mode")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn fit_to_dpi(
+    image_bytes: &[u8],
+    width_mm: &[u8],
+    dpi: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::FitToDpi(FitToDpiArgs {
+        width_mm: args::f32_arg(width_mm, "width_mm")?,
+        dpi: args::f32_arg(dpi, "dpi")?,
+        filter: ops::Filter::default(),
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn rotate90(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Rotate90], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn rotate180(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Rotate180], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn rotate270(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Rotate270], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn fliph(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Fliph], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn flipv(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Flipv], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn rotate(
+    image_bytes: &[u8],
+    angle: &[u8],
+    background: &[u8],
+    interpolation: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Rotate(RotateArgs {
+        angle: args::f32_arg(angle, "angle")?,
+        background: args::rgba_arg(background, "background")?,
+        interpolation: args::str_arg(interpolation, "interpolation")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn pipeline(image_bytes: &[u8], ops_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let operations: Vec<Operation> = args::decode(ops_bytes)?;
+    apply_operations(image_bytes, &operations, &Options::default())
+}
+
+//converts the image to an SVG, see `trace::TraceArgs` for the options
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn trace(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let a: trace::TraceArgs = args::decode(args_bytes)?;
+    let options: Options = args::decode(args_bytes)?;
+    let (img, _) = get_decoded_image_from_bytes(image_bytes, options.auto_orient, &options.limits)?;
+    trace::trace(&img, &a, &options.limits).map(String::into_bytes)
+}
+
+fn apply_cbor<T: DeserializeOwned>(
+    image_bytes: &[u8],
+    args_bytes: &[u8],
+    operation: fn(T) -> Operation,
+) -> Result<Vec<u8>, GraynessError> {
+    //both ignore the fields they don't know, so the options can simply be decoded from the same dictionary
+    let op = operation(args::decode(args_bytes)?);
+    let options: Options = args::decode(args_bytes)?;
+    apply_operations(image_bytes, &[op], &options)
+}
+
+#[derive(Deserialize)]
+struct PipelineArgs {
+    operations: Vec<Operation>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn pipeline_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let pipeline: PipelineArgs = args::decode(args_bytes)?;
+    let options: Options = args::decode(args_bytes)?;
+    apply_operations(image_bytes, &pipeline.operations, &options)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn decode_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let options: Options = args::decode(args_bytes)?;
+    decode_image(image_bytes, &options)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn mask_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Mask)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn crop_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Crop)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn blur_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Blur)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn transparency_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Transparency)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn brighten_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Brighten)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn huerotate_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Huerotate)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn matrix_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Matrix)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn resize_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Resize)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn fit_to_dpi_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::FitToDpi)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn rotate_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Rotate)
 }
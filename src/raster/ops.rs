@@ -0,0 +1,321 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Pixel, Rgba, RgbaImage};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<Filter> for FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    //scale to exactly the given size, ignoring the aspect ratio
+    Exact,
+    //scale to fit within the given size, preserving the aspect ratio
+    #[default]
+    Fit,
+    //scale to cover the given size, preserving the aspect ratio and cropping the overflow
+    Fill,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+pub fn grayscale(img: &DynamicImage) -> DynamicImage {
+    img.grayscale()
+}
+
+pub fn crop(mut img: DynamicImage, x: u32, y: u32, width: u32, height: u32) -> DynamicImage {
+    img.crop(x, y, width, height)
+}
+
+pub fn blur(img: &DynamicImage, sigma: f32) -> DynamicImage {
+    img.blur(sigma)
+}
+
+pub fn transparency(img: &DynamicImage, alpha: u8) -> DynamicImage {
+    let mut res = img.to_rgba8();
+
+    for y in 0..res.height() {
+        for x in 0..res.width() {
+            let pixel = res.get_pixel_mut(x, y);
+            pixel.apply_with_alpha(|ch| ch, |_| alpha);
+        }
+    }
+
+    DynamicImage::ImageRgba8(res)
+}
+
+pub fn invert(mut img: DynamicImage) -> DynamicImage {
+    img.invert();
+    img
+}
+
+pub fn brighten(img: &DynamicImage, amount: i32) -> DynamicImage {
+    img.brighten(amount)
+}
+
+pub fn huerotate(img: &DynamicImage, amount: i32) -> DynamicImage {
+    img.huerotate(amount)
+}
+
+pub fn resize(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: Filter,
+    mode: ResizeMode,
+) -> DynamicImage {
+    match mode {
+        ResizeMode::Exact => img.resize_exact(width, height, filter.into()),
+        ResizeMode::Fit => img.resize(width, height, filter.into()),
+        ResizeMode::Fill => img.resize_to_fill(width, height, filter.into()),
+    }
+}
+
+//the size of the largest image `resize` allocates, to check it against the limits before resizing.
+//filling first scales the image to cover the whole area and then crops it, like the image crate does
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn resized_dimensions(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    mode: ResizeMode,
+) -> (u32, u32) {
+    let width_ratio = f64::from(width) / f64::from(img.width());
+    let height_ratio = f64::from(height) / f64::from(img.height());
+    let ratio = match mode {
+        ResizeMode::Exact => return (width, height),
+        ResizeMode::Fit => width_ratio.min(height_ratio),
+        ResizeMode::Fill => width_ratio.max(height_ratio),
+    };
+    (
+        (f64::from(img.width()) * ratio).round().max(1.0) as u32,
+        (f64::from(img.height()) * ratio).round().max(1.0) as u32,
+    )
+}
+
+//the bytes allocated to resample the image to the given size: the result and the f32 buffer
+//holding the image after resampling its columns, before resampling its rows
+pub fn resize_memory(img: &DynamicImage, width: u32, height: u32) -> u64 {
+    let result = (u64::from(width) * u64::from(height)).saturating_mul(4);
+    let buffer = (u64::from(img.width()) * u64::from(height)).saturating_mul(16);
+    result.saturating_add(buffer)
+}
+
+//the width in pixels of an image printed at the given width and resolution, both of which must be positive.
+//the cast saturates for huge print sizes, which is harmless since `fit_to_dpi` never upsamples
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn dpi_width(width_mm: f32, dpi: f32) -> u32 {
+    (width_mm / 25.4 * dpi).round().max(1.0) as u32
+}
+
+//downsamples the image so it has at most the given resolution when printed at the given width, never upsamples
+pub fn fit_to_dpi(img: DynamicImage, width_mm: f32, dpi: f32, filter: Filter) -> DynamicImage {
+    let max_width = dpi_width(width_mm, dpi);
+    if img.width() <= max_width {
+        return img;
+    }
+    img.resize(max_width, u32::MAX, filter.into())
+}
+
+pub fn rotate90(img: &DynamicImage) -> DynamicImage {
+    img.rotate90()
+}
+
+pub fn rotate180(img: &DynamicImage) -> DynamicImage {
+    img.rotate180()
+}
+
+pub fn rotate270(img: &DynamicImage) -> DynamicImage {
+    img.rotate270()
+}
+
+pub fn fliph(img: &DynamicImage) -> DynamicImage {
+    img.fliph()
+}
+
+pub fn flipv(img: &DynamicImage) -> DynamicImage {
+    img.flipv()
+}
+
+//the size of the result of `rotate`, the bounding box of the rotated image, to check it against the limits before rotating
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn rotated_dimensions(img: &DynamicImage, angle: f32) -> (u32, u32) {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    (
+        (width * cos.abs() + height * sin.abs()).round().max(1.0) as u32,
+        (width * sin.abs() + height * cos.abs()).round().max(1.0) as u32,
+    )
+}
+
+//rotates clockwise by an arbitrary angle in degrees, enlarging the canvas to fit and filling the corners with the background
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn rotate(
+    img: &DynamicImage,
+    angle: f32,
+    background: Rgba<u8>,
+    interpolation: Interpolation,
+) -> DynamicImage {
+    let (new_width, new_height) = rotated_dimensions(img, angle);
+    let src = img.to_rgba8();
+    let (width, height) = (src.width() as f32, src.height() as f32);
+    let (sin, cos) = angle.to_radians().sin_cos();
+
+    let res = RgbaImage::from_fn(new_width, new_height, |x, y| {
+        //rotate the pixel center back into the source image
+        let dx = x as f32 + 0.5 - new_width as f32 / 2.0;
+        let dy = y as f32 + 0.5 - new_height as f32 / 2.0;
+        let sx = dx * cos + dy * sin + width / 2.0 - 0.5;
+        let sy = -dx * sin + dy * cos + height / 2.0 - 0.5;
+        match interpolation {
+            Interpolation::Nearest => {
+                source_pixel(&src, sx.round() as i64, sy.round() as i64).unwrap_or(background)
+            }
+            Interpolation::Bilinear => bilinear_pixel(&src, sx, sy, background),
+        }
+    });
+
+    DynamicImage::ImageRgba8(res)
+}
+
+fn source_pixel(src: &RgbaImage, x: i64, y: i64) -> Option<Rgba<u8>> {
+    let x = u32::try_from(x).ok()?;
+    let y = u32::try_from(y).ok()?;
+    src.get_pixel_checked(x, y).copied()
+}
+
+//interpolates with premultiplied alpha, so transparent neighbours don't darken the edges
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn bilinear_pixel(src: &RgbaImage, x: f32, y: f32, background: Rgba<u8>) -> Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut acc = [0.0f32; 4];
+
+    for (ox, oy, weight) in [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ] {
+        let pixel = source_pixel(src, x0 as i64 + ox, y0 as i64 + oy).unwrap_or(background);
+        let alpha = f32::from(pixel[3]) * weight;
+        for c in 0..3 {
+            acc[c] += f32::from(pixel[c]) * alpha;
+        }
+        acc[3] += alpha;
+    }
+
+    if acc[3] <= 0.0 {
+        return background;
+    }
+    Rgba([
+        (acc[0] / acc[3]).round().clamp(0.0, 255.0) as u8,
+        (acc[1] / acc[3]).round().clamp(0.0, 255.0) as u8,
+        (acc[2] / acc[3]).round().clamp(0.0, 255.0) as u8,
+        acc[3].round().clamp(0.0, 255.0) as u8,
+    ])
+}
+
+pub fn mask(img: &DynamicImage, mask: &DynamicImage, use_alpha: bool) -> DynamicImage {
+    let (target_width, target_height) = img.dimensions();
+    let resized;
+    let mask = if mask.dimensions() == img.dimensions() {
+        mask
+    } else {
+        resized = mask.resize_exact(
+            target_width,
+            target_height,
+            image::imageops::FilterType::Nearest,
+        );
+        &resized
+    };
+
+    let mut output = RgbaImage::new(target_width, target_height);
+
+    for y in 0..target_height {
+        for x in 0..target_width {
+            let mut pixel = img.get_pixel(x, y);
+            let mask_pixel = mask.get_pixel(x, y);
+            let target_alpha = f32::from(pixel[3]) / 255.0;
+            let mask_alpha = if use_alpha {
+                f32::from(mask_pixel[3]) / 255.0
+            } else {
+                f32::from(mask_pixel.to_luma()[0]) / 255.0
+            };
+
+            //pixel values are always positive, no precision is lost since the floats were only intermediate anyway.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let new_alpha = (target_alpha * mask_alpha * 255.0).round() as u8;
+            pixel[3] = new_alpha;
+            output.put_pixel(x, y, pixel);
+        }
+    }
+
+    DynamicImage::ImageRgba8(output)
+}
+
+//the matrix is given row by row, each row consisting of the factors for r, g, b, a and a constant offset
+pub fn matrix(img: &DynamicImage, m: &[f32; 20]) -> DynamicImage {
+    let mut res = img.to_rgba8();
+
+    for y in 0..res.height() {
+        for x in 0..res.width() {
+            let pixel = res.get_pixel_mut(x, y);
+            let r = f32::from(pixel[0]);
+            let g = f32::from(pixel[1]);
+            let b = f32::from(pixel[2]);
+            let a = f32::from(pixel[3]);
+
+            let nr = m[0] * r + m[1] * g + m[2] * b + m[3] * a + m[4] * 255.0;
+            let ng = m[5] * r + m[6] * g + m[7] * b + m[8] * a + m[9] * 255.0;
+
+            let nb = m[10] * r + m[11] * g + m[12] * b + m[13] * a + m[14] * 255.0;
+            let na = m[15] * r + m[16] * g + m[17] * b + m[18] * a + m[19] * 255.0;
+
+            pixel[0] = nr.clamp(0.0, 255.0) as u8;
+            pixel[1] = ng.clamp(0.0, 255.0) as u8;
+            pixel[2] = nb.clamp(0.0, 255.0) as u8;
+            pixel[3] = na.clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    DynamicImage::ImageRgba8(res)
+}
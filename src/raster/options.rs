@@ -0,0 +1,107 @@
+use image::ImageFormat;
+#[cfg(feature = "png")]
+use image::codecs::png::{CompressionType, FilterType};
+use serde::Deserialize;
+use serde::de::{self, Deserializer};
+
+use crate::limits::Limits;
+
+//settings concerning the whole image rather than a single operation, given alongside the arguments of the `_cbor` functions
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    //rotate and flip the image according to its EXIF orientation when decoding it, like typst does
+    pub auto_orient: bool,
+    //JPEG quality from 1 to 100
+    pub quality: u8,
+    pub compression: Compression,
+    pub png_filter: PngFilter,
+    //the WebP encoder only supports lossless encoding, so lossy encoding is rejected instead of silently ignored
+    pub lossless: bool,
+    //the format to encode the result in, instead of the format of the input image
+    pub format: Option<OutputFormat>,
+    pub limits: Limits,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            auto_orient: true,
+            quality: 75,
+            compression: Compression::default(),
+            png_filter: PngFilter::default(),
+            lossless: true,
+            format: None,
+            limits: Limits::default(),
+        }
+    }
+}
+
+//PNG compression level
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    #[default]
+    Fast,
+    Default,
+    Best,
+}
+
+#[cfg(feature = "png")]
+impl From<Compression> for CompressionType {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => CompressionType::Uncompressed,
+            Compression::Fast => CompressionType::Fast,
+            Compression::Default => CompressionType::Default,
+            Compression::Best => CompressionType::Best,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+}
+
+#[cfg(feature = "png")]
+impl From<PngFilter> for FilterType {
+    fn from(filter: PngFilter) -> Self {
+        match filter {
+            PngFilter::None => FilterType::NoFilter,
+            PngFilter::Sub => FilterType::Sub,
+            PngFilter::Up => FilterType::Up,
+            PngFilter::Avg => FilterType::Avg,
+            PngFilter::Paeth => FilterType::Paeth,
+            PngFilter::Adaptive => FilterType::Adaptive,
+        }
+    }
+}
+
+//an image format named by its usual file extension, e.g. "jpg" or "png"
+#[derive(Debug, Clone, Copy)]
+pub struct OutputFormat(pub ImageFormat);
+
+impl<'de> Deserialize<'de> for OutputFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match ImageFormat::from_extension(&name) {
+            Some(format) if format.writing_enabled() => Ok(OutputFormat(format)),
+            //the codecs are cargo features, and some formats like DDS can only be read
+            Some(_) => Err(de::Error::custom(format!(
+                "output format `{name}` can't be written or is not compiled into this build of the plugin"
+            ))),
+            None => Err(de::Error::custom(format!(
+                "unsupported output format `{name}`"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use image::{DynamicImage, Rgba};
+use serde::Deserialize;
+
+use super::get_decoded_image_from_bytes;
+use super::ops::{self, Filter, Interpolation, ResizeMode};
+use super::options::Options;
+use crate::args;
+use crate::error::GraynessError;
+
+#[derive(Debug, Deserialize)]
+pub struct CropArgs {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropArgs {
+    //the region has to be within the image, which would otherwise silently shrink it, possibly to nothing
+    fn check(&self, img: &DynamicImage) -> Result<(), GraynessError> {
+        let axes = [
+            ("x", self.x, "width", self.width, img.width()),
+            ("y", self.y, "height", self.height, img.height()),
+        ];
+        for (start_name, start, size_name, size, max) in axes {
+            if start >= max {
+                return Err(GraynessError::invalid_argument(
+                    start_name,
+                    format!("expected a value below the image {size_name} of {max}, got {start}"),
+                ));
+            }
+            if size == 0 || size > max - start {
+                return Err(GraynessError::invalid_argument(
+                    size_name,
+                    format!("expected a value from 1 to {}, got {size}", max - start),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlurArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub sigma: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransparencyArgs {
+    pub alpha: u8,
+}
+
+//shared by brighten and huerotate
+#[derive(Debug, Deserialize)]
+pub struct AmountArgs {
+    pub amount: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MatrixArgs {
+    #[serde(deserialize_with = "args::float_matrix")]
+    pub matrix: [f32; 20],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResizeArgs {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub mode: ResizeMode,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FitToDpiArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub width_mm: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub dpi: f32,
+    #[serde(default)]
+    pub filter: Filter,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub angle: f32,
+    //RGBA, transparent by default
+    #[serde(default)]
+    pub background: [u8; 4],
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaskArgs {
+    #[serde(with = "serde_bytes")]
+    pub mask: Vec<u8>,
+    #[serde(default)]
+    pub use_alpha: bool,
+}
+
+//a single step of a pipeline, encoded as a map with an "op" key naming the operation, e.g. (op: "blur", sigma: 2.0)
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Grayscale,
+    Crop(CropArgs),
+    Blur(BlurArgs),
+    Transparency(TransparencyArgs),
+    Invert,
+    Brighten(AmountArgs),
+    Huerotate(AmountArgs),
+    Matrix(MatrixArgs),
+    Mask(MaskArgs),
+    Resize(ResizeArgs),
+    FitToDpi(FitToDpiArgs),
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Fliph,
+    Flipv,
+    Rotate(RotateArgs),
+}
+
+impl Operation {
+    pub fn apply(
+        &self,
+        img: DynamicImage,
+        options: &Options,
+    ) -> Result<DynamicImage, GraynessError> {
+        let limits = &options.limits;
+        Ok(match self {
+            Operation::Grayscale => ops::grayscale(&img),
+            Operation::Crop(a) => {
+                a.check(&img)?;
+                ops::crop(img, a.x, a.y, a.width, a.height)
+            }
+            Operation::Blur(a) => {
+                //the image crate panics on these, which would abort the whole compilation
+                if !a.sigma.is_finite() || a.sigma <= 0.0 {
+                    return Err(GraynessError::invalid_argument(
+                        "sigma",
+                        format!("expected a positive number, got {}", a.sigma),
+                    ));
+                }
+                ops::blur(&img, a.sigma)
+            }
+            Operation::Transparency(a) => ops::transparency(&img, a.alpha),
+            Operation::Invert => ops::invert(img),
+            Operation::Brighten(a) => ops::brighten(&img, a.amount),
+            Operation::Huerotate(a) => ops::huerotate(&img, a.amount),
+            Operation::Matrix(a) => ops::matrix(&img, &a.matrix),
+            Operation::Mask(a) => {
+                let (mask, _) = get_decoded_image_from_bytes(&a.mask, options.auto_orient, limits)?;
+                ops::mask(&img, &mask, a.use_alpha)
+            }
+            Operation::Resize(a) => {
+                for (name, value) in [("width", a.width), ("height", a.height)] {
+                    if value == 0 {
+                        return Err(GraynessError::invalid_argument(
+                            name,
+                            "expected a positive size, got 0",
+                        ));
+                    }
+                }
+                let (width, height) = ops::resized_dimensions(&img, a.width, a.height, a.mode);
+                limits.check_image(width, height, ops::resize_memory(&img, width, height))?;
+                ops::resize(&img, a.width, a.height, a.filter, a.mode)
+            }
+            Operation::FitToDpi(a) => {
+                for (name, value) in [("width_mm", a.width_mm), ("dpi", a.dpi)] {
+                    if !value.is_finite() || value <= 0.0 {
+                        return Err(GraynessError::invalid_argument(
+                            name,
+                            format!("expected a positive number, got {value}"),
+                        ));
+                    }
+                }
+                let width = ops::dpi_width(a.width_mm, a.dpi).min(img.width());
+                let (width, height) =
+                    ops::resized_dimensions(&img, width, u32::MAX, ResizeMode::Fit);
+                limits.check_image(width, height, ops::resize_memory(&img, width, height))?;
+                ops::fit_to_dpi(img, a.width_mm, a.dpi, a.filter)
+            }
+            Operation::Rotate90 => ops::rotate90(&img),
+            Operation::Rotate180 => ops::rotate180(&img),
+            Operation::Rotate270 => ops::rotate270(&img),
+            Operation::Fliph => ops::fliph(&img),
+            Operation::Flipv => ops::flipv(&img),
+            Operation::Rotate(a) => {
+                let (width, height) = ops::rotated_dimensions(&img, a.angle);
+                limits.check_size(width, height)?;
+                ops::rotate(&img, a.angle, Rgba(a.background), a.interpolation)
+            }
+        })
+    }
+
+    //operations which produce meaningful alpha values, requiring the result to be written as PNG
+    pub fn needs_alpha(&self) -> bool {
+        match self {
+            Operation::Transparency(_) | Operation::Matrix(_) | Operation::Mask(_) => true,
+            //the corners are only transparent with a transparent background
+            Operation::Rotate(a) => a.background[3] < 255,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::Deserialize;
+use std::fmt::Write;
+
+use crate::args;
+use crate::error::GraynessError;
+use crate::limits::Limits;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceMode {
+    //quantize the image to a few colors and trace each of them
+    #[default]
+    Color,
+    //trace the pixels darker than the threshold in black
+    Bw,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TraceArgs {
+    pub mode: TraceMode,
+    //number of colors in color mode
+    pub colors: u8,
+    //luminance below which a pixel is traced in bw mode
+    pub threshold: u8,
+    //maximum distance in pixels between the outline of the pixels and the simplified path
+    #[serde(deserialize_with = "args::float")]
+    pub tolerance: f32,
+    //draw curves instead of straight lines where the path doesn't turn sharply
+    pub smooth: bool,
+    //turns sharper than this angle in degrees are kept as corners when smoothing
+    #[serde(deserialize_with = "args::float")]
+    pub corner_threshold: f32,
+    //regions with fewer pixels are dropped as noise
+    pub speckle: u32,
+}
+
+impl Default for TraceArgs {
+    fn default() -> Self {
+        TraceArgs {
+            mode: TraceMode::default(),
+            colors: 8,
+            threshold: 128,
+            tolerance: 1.0,
+            smooth: true,
+            corner_threshold: 60.0,
+            speckle: 4,
+        }
+    }
+}
+
+type Point = (f32, f32);
+
+//marks transparent pixels in the layer ranks, which can't be more than 64 colors
+const NO_LAYER: u8 = u8::MAX;
+
+//the memory needed while tracing, besides the decoded image: its RGBA copy, the layer of every pixel, the mask of one layer,
+//the edges leaving every pixel corner and the outlines, which have at most four corners per pixel.
+//the corners are indexed with i32, so their number is limited as well
+fn check_memory(width: u32, height: u32, limits: &Limits) -> Result<(), GraynessError> {
+    let (w, h) = (u64::from(width), u64::from(height));
+    let edges = (w + 1) * (h + 1) * std::mem::size_of::<[Option<(i32, i32)>; 2]>() as u64;
+    let outlines = 4 * w * h * std::mem::size_of::<(i32, i32)>() as u64;
+    if 6 * w * h + edges + outlines > limits.max_bytes() || (w + 1) * (h + 1) > i32::MAX as u64 {
+        return Err(GraynessError::LimitExceeded(format!(
+            "Tracing an image of {width}x{height} pixels exceeds the memory limit of {} MiB, see the `max_memory` limit",
+            limits.max_memory
+        )));
+    }
+    Ok(())
+}
+
+//converts the image to an SVG with one path per color. In color mode the layers are stacked from the most to the least frequent color,
+//each layer also covering the pixels of the layers above it, so the paths don't leave gaps along their shared edges
+pub fn trace(img: &DynamicImage, a: &TraceArgs, limits: &Limits) -> Result<String, GraynessError> {
+    check_memory(img.width(), img.height(), limits)?;
+    let img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+
+    //the colors of the layers, and the layer of every pixel
+    let (colors, ranks) = match a.mode {
+        TraceMode::Bw => {
+            let ranks = img
+                .pixels()
+                .map(|p| {
+                    if opaque(p) && luminance(p) < f32::from(a.threshold) {
+                        0
+                    } else {
+                        NO_LAYER
+                    }
+                })
+                .collect();
+            (vec![[0, 0, 0]], ranks)
+        }
+        TraceMode::Color => {
+            if !(2..=64).contains(&a.colors) {
+                return Err(GraynessError::invalid_argument(
+                    "colors",
+                    format!("expected a value from 2 to 64, got {}", a.colors),
+                ));
+            }
+            color_layers(&img, usize::from(a.colors))
+        }
+    };
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    //the masks are built one at a time, since each of them is as large as the image
+    for (layer, [r, g, b]) in colors.into_iter().enumerate() {
+        let mask: Vec<bool> = ranks
+            .iter()
+            .map(|&rank| rank != NO_LAYER && usize::from(rank) >= layer)
+            .collect();
+        let mut d = String::new();
+        for outline in outlines(&mask, width, height) {
+            //the speckle size is compared in pixels, which are exact far beyond any sensible value
+            #[allow(clippy::cast_precision_loss)]
+            if area(&outline) < a.speckle as f32 {
+                continue;
+            }
+            let polygon = simplify(&outline, a.tolerance);
+            write_path(&mut d, &polygon, a.smooth, a.corner_threshold);
+        }
+        if !d.is_empty() {
+            let _ = write!(
+                svg,
+                r##"<path fill="#{r:02x}{g:02x}{b:02x}" fill-rule="evenodd" d="{d}"/>"##
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+fn opaque(p: &Rgba<u8>) -> bool {
+    p[3] >= 128
+}
+
+fn luminance(p: &Rgba<u8>) -> f32 {
+    0.2126 * f32::from(p[0]) + 0.7152 * f32::from(p[1]) + 0.0722 * f32::from(p[2])
+}
+
+fn distance2(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+
+//k-means on a histogram of the colors reduced to 5 bits per channel, starting from the most frequent color
+//and repeatedly adding the color which is both frequent and far away from the chosen ones.
+//returns the colors from the most to the least frequent, and the index of the color of every pixel
+//the pixel counts are only used as weights, where rounding them for huge images doesn't matter
+#[allow(clippy::cast_precision_loss)]
+fn color_layers(img: &RgbaImage, colors: usize) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let bin = |p: &Rgba<u8>| {
+        ((usize::from(p[0]) >> 3) << 10)
+            | ((usize::from(p[1]) >> 3) << 5)
+            | (usize::from(p[2]) >> 3)
+    };
+    let mut counts = vec![0u32; 1 << 15];
+    let mut sums = vec![[0f32; 3]; 1 << 15];
+    for p in img.pixels().filter(|p| opaque(p)) {
+        let i = bin(p);
+        counts[i] += 1;
+        for c in 0..3 {
+            sums[i][c] += f32::from(p[c]);
+        }
+    }
+    let histogram: Vec<([f32; 3], f32)> = counts
+        .iter()
+        .zip(&sums)
+        .filter(|(n, _)| **n > 0)
+        .map(|(&n, s)| (s.map(|c| c / n as f32), n as f32))
+        .collect();
+    if histogram.is_empty() {
+        return (Vec::new(), Vec::new());
+    }
+
+    let mut centers = vec![
+        histogram
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0,
+    ];
+    while centers.len() < colors.min(histogram.len()) {
+        let score = |(color, n): &([f32; 3], f32)| {
+            n * centers
+                .iter()
+                .map(|c| distance2(*color, *c))
+                .fold(f32::MAX, f32::min)
+        };
+        let next = histogram
+            .iter()
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+            .unwrap();
+        if score(next) == 0.0 {
+            break;
+        }
+        centers.push(next.0);
+    }
+
+    let nearest = |color: [f32; 3], centers: &[[f32; 3]]| {
+        (0..centers.len())
+            .min_by(|&a, &b| distance2(color, centers[a]).total_cmp(&distance2(color, centers[b])))
+            .unwrap()
+    };
+    for _ in 0..10 {
+        let mut totals = vec![([0f32; 3], 0f32); centers.len()];
+        for (color, n) in &histogram {
+            let t = &mut totals[nearest(*color, &centers)];
+            for (sum, c) in t.0.iter_mut().zip(color) {
+                *sum += c * n;
+            }
+            t.1 += n;
+        }
+        for (center, (sum, n)) in centers.iter_mut().zip(totals) {
+            if n > 0.0 {
+                *center = sum.map(|c| c / n);
+            }
+        }
+    }
+
+    //the cluster of every histogram bin, and the pixel count per cluster to order the layers
+    let mut cluster = vec![usize::MAX; 1 << 15];
+    let mut sizes = vec![0u32; centers.len()];
+    for (i, n) in counts.iter().enumerate().filter(|(_, n)| **n > 0) {
+        let k = nearest(sums[i].map(|c| c / *n as f32), &centers);
+        cluster[i] = k;
+        sizes[k] += n;
+    }
+    let mut order: Vec<usize> = (0..centers.len()).filter(|&k| sizes[k] > 0).collect();
+    order.sort_by_key(|&k| std::cmp::Reverse(sizes[k]));
+    let mut rank = vec![NO_LAYER; centers.len()];
+    for (r, &k) in order.iter().enumerate() {
+        //there are at most 64 colors
+        #[allow(clippy::cast_possible_truncation)]
+        let r = r as u8;
+        rank[k] = r;
+    }
+
+    let ranks = img
+        .pixels()
+        .map(|p| {
+            if opaque(p) {
+                rank[cluster[bin(p)]]
+            } else {
+                NO_LAYER
+            }
+        })
+        .collect();
+    //the centers are averages of colors, so they are within 0 to 255
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let colors = order
+        .iter()
+        .map(|&k| centers[k].map(|c| c.round() as u8))
+        .collect();
+    (colors, ranks)
+}
+
+//the closed outlines along the pixel edges of the mask, clockwise around regions and counterclockwise around holes.
+//`check_memory` keeps the number of pixel corners within i32, and indices are only taken of corners inside the image
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss
+)]
+fn outlines(mask: &[bool], width: u32, height: u32) -> Vec<Vec<(i32, i32)>> {
+    let (w, h) = (width as i32, height as i32);
+    let inside = |x: i32, y: i32| x >= 0 && y >= 0 && x < w && y < h && mask[(y * w + x) as usize];
+    let vertex = |x: i32, y: i32| (y * (w + 1) + x) as usize;
+
+    //up to two edges leave a vertex where two pixels only touch diagonally
+    let mut edges = vec![[None; 2]; ((w + 1) * (h + 1)) as usize];
+    let mut add = |from: (i32, i32), to: (i32, i32)| {
+        let slots: &mut [Option<(i32, i32)>; 2] = &mut edges[vertex(from.0, from.1)];
+        let slot = if slots[0].is_none() { 0 } else { 1 };
+        slots[slot] = Some(to);
+    };
+    for y in 0..h {
+        for x in 0..w {
+            if !inside(x, y) {
+                continue;
+            }
+            if !inside(x, y - 1) {
+                add((x, y), (x + 1, y));
+            }
+            if !inside(x + 1, y) {
+                add((x + 1, y), (x + 1, y + 1));
+            }
+            if !inside(x, y + 1) {
+                add((x + 1, y + 1), (x, y + 1));
+            }
+            if !inside(x - 1, y) {
+                add((x, y + 1), (x, y));
+            }
+        }
+    }
+
+    let mut loops = Vec::new();
+    for start in 0..edges.len() {
+        while let Some(first) = edges[start][0].or(edges[start][1]) {
+            let origin = ((start as i32) % (w + 1), (start as i32) / (w + 1));
+            let mut points = vec![origin];
+            let mut from = origin;
+            let mut to = first;
+            loop {
+                let slots = &mut edges[vertex(from.0, from.1)];
+                let slot = slots.iter().position(|s| *s == Some(to)).unwrap();
+                slots[slot] = None;
+                if to == origin {
+                    break;
+                }
+                points.push(to);
+                //at diagonal contacts, turn right so the touching pixels get separate outlines
+                let dir = (to.0 - from.0, to.1 - from.1);
+                let next = edges[vertex(to.0, to.1)]
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .max_by_key(|n| dir.0 * (n.1 - to.1) - dir.1 * (n.0 - to.0))
+                    .unwrap();
+                from = to;
+                to = next;
+            }
+            loops.push(corners(&points));
+        }
+    }
+    loops
+}
+
+//only keeps the points where the outline changes direction
+fn corners(points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let n = points.len();
+    (0..n)
+        .filter(|&i| {
+            let (p, c, q) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            (c.0 - p.0, c.1 - p.1) != (q.0 - c.0, q.1 - c.1)
+        })
+        .map(|i| points[i])
+        .collect()
+}
+
+fn area(points: &[(i32, i32)]) -> f32 {
+    let n = points.len();
+    let twice: i64 = (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            i64::from(a.0) * i64::from(b.1) - i64::from(b.0) * i64::from(a.1)
+        })
+        .sum();
+    //only compared against the speckle size, so rounding huge areas doesn't matter
+    #[allow(clippy::cast_precision_loss)]
+    let twice = twice.abs() as f32;
+    twice / 2.0
+}
+
+//Ramer-Douglas-Peucker on both halves of the closed outline, split at the point farthest from the first one
+fn simplify(points: &[(i32, i32)], tolerance: f32) -> Vec<Point> {
+    //coordinates are exact in f32 up to 2^24 pixels, far beyond the size limits
+    #[allow(clippy::cast_precision_loss)]
+    let points: Vec<Point> = points.iter().map(|&(x, y)| (x as f32, y as f32)).collect();
+    if tolerance <= 0.0 || points.len() <= 4 {
+        return points;
+    }
+    let far = (1..points.len())
+        .max_by(|&a, &b| dist2(points[0], points[a]).total_cmp(&dist2(points[0], points[b])))
+        .unwrap();
+    let mut second = points[far..].to_vec();
+    second.push(points[0]);
+
+    let mut result = rdp(&points[..=far], tolerance);
+    result.pop();
+    result.extend(rdp(&second, tolerance));
+    result.pop();
+    result
+}
+
+//keeps the point farthest from the line between the ends of a range if it exceeds the tolerance, and continues with both halves.
+//the ranges are kept on a stack instead of recursing, since long outlines could overflow the small call stack of plugins
+fn rdp(points: &[Point], tolerance: f32) -> Vec<Point> {
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let (first, last) = (points[start], points[end]);
+        let farthest = (start + 1..end)
+            .map(|i| (i, line_distance(points[i], first, last)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, d)) = farthest
+            && d > tolerance
+        {
+            keep[i] = true;
+            ranges.push((start, i));
+            ranges.push((i, end));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(&p, keep)| keep.then_some(p))
+        .collect()
+}
+
+fn dist2(a: Point, b: Point) -> f32 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+fn line_distance(p: Point, a: Point, b: Point) -> f32 {
+    let len = dist2(a, b).sqrt();
+    if len == 0.0 {
+        return dist2(p, a).sqrt();
+    }
+    ((b.0 - a.0) * (a.1 - p.1) - (a.0 - p.0) * (b.1 - a.1)).abs() / len
+}
+
+//starts in the middle of the last edge, and either curves through the middles of the edges around each point or keeps it as a corner
+fn write_path(d: &mut String, polygon: &[Point], smooth: bool, corner_threshold: f32) {
+    let n = polygon.len();
+    if n < 3 {
+        return;
+    }
+    let mid = |a: Point, b: Point| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    let start = mid(polygon[n - 1], polygon[0]);
+    let _ = write!(d, "M{} {}", num(start.0), num(start.1));
+    for i in 0..n {
+        let (p, c, q) = (polygon[(i + n - 1) % n], polygon[i], polygon[(i + 1) % n]);
+        let m = mid(c, q);
+        //the angle between the incoming and outgoing direction
+        let (u, v) = ((c.0 - p.0, c.1 - p.1), (q.0 - c.0, q.1 - c.1));
+        let turn = (u.0 * v.1 - u.1 * v.0).atan2(u.0 * v.0 + u.1 * v.1);
+        if smooth && turn.to_degrees().abs() < corner_threshold {
+            let _ = write!(d, "Q{} {} {} {}", num(c.0), num(c.1), num(m.0), num(m.1));
+        } else {
+            let _ = write!(d, "L{} {}L{} {}", num(c.0), num(c.1), num(m.0), num(m.1));
+        }
+    }
+    d.push('Z');
+}
+
+fn num(v: f32) -> String {
+    let s = format!("{:.2}", v);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
@@ -0,0 +1,252 @@
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt::Write;
+use std::io::{Read, Write as _};
+use xml::common::Position;
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+use xmltree::{Element, EmitterConfig, XMLNode};
+
+use crate::error::GraynessError;
+use crate::limits::Limits;
+
+//an SVG file, keeping the comments, processing instructions and doctype around the root element.
+//xmltree is only used to hold and write the tree, since its parser drops namespace prefixes from attributes (e.g. `xlink:href`),
+//whitespace-only text and everything outside the root element
+pub struct Document {
+    //whether the file was read from (and will be written as) a gzip-compressed SVGZ file
+    pub compressed: bool,
+    pub prolog: Vec<XMLNode>,
+    pub doctype: Option<String>,
+    pub root: Element,
+    pub epilog: Vec<XMLNode>,
+}
+
+impl Document {
+    pub fn parse(bytes: &[u8], limits: &Limits) -> Result<Document, GraynessError> {
+        let compressed = is_gzip(bytes);
+        let decompressed;
+        let bytes = if compressed {
+            decompressed = decompress(bytes, limits)?;
+            decompressed.as_slice()
+        } else {
+            bytes
+        };
+
+        let config = ParserConfig::new()
+            .ignore_comments(false)
+            .whitespace_to_characters(true);
+        let mut reader = EventReader::new_with_config(bytes, config);
+        let mut prolog = Vec::new();
+        let mut doctype = None;
+        let mut root = None;
+        let mut epilog = Vec::new();
+        let mut elements = 0;
+
+        loop {
+            let event = reader.next().map_err(parse_error)?;
+            let nodes = if root.is_some() {
+                &mut epilog
+            } else {
+                &mut prolog
+            };
+            match event {
+                XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                } => {
+                    limits.check_element(1, &mut elements)?;
+                    let elem = new_element(name, attributes, namespace);
+                    root = Some(build(&mut reader, elem, 1, &mut elements, limits)?);
+                }
+                XmlEvent::Comment(comment) => nodes.push(XMLNode::Comment(comment)),
+                XmlEvent::ProcessingInstruction { name, data } => {
+                    nodes.push(XMLNode::ProcessingInstruction(name, data));
+                }
+                XmlEvent::Doctype { syntax } => doctype = Some(syntax),
+                XmlEvent::EndDocument => break,
+                _ => {}
+            }
+        }
+
+        Ok(Document {
+            compressed,
+            prolog,
+            doctype,
+            root: root.ok_or(GraynessError::InvalidSvg(
+                "The SVG has no root element".to_string(),
+            ))?,
+            epilog,
+        })
+    }
+
+    pub fn write(&self) -> Result<Vec<u8>, GraynessError> {
+        let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        write_nodes(&mut out, &self.prolog);
+        if let Some(doctype) = &self.doctype {
+            out.push_str(doctype);
+        }
+
+        let mut svg_output = out.into_bytes();
+        let config = EmitterConfig::new().write_document_declaration(false);
+        self.root
+            .write_with_config(&mut svg_output, config)
+            .map_err(|e| GraynessError::EncodeFailed(format!("Could not write SVG bytes: {e}")))?;
+
+        let mut out = String::new();
+        write_nodes(&mut out, &self.epilog);
+        svg_output.extend(out.into_bytes());
+
+        if !self.compressed {
+            return Ok(svg_output);
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&svg_output)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| GraynessError::EncodeFailed(format!("Could not compress SVG bytes: {e}")))
+    }
+}
+
+//SVGZ files are plain gzip streams, recognized by their magic bytes
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
+
+//a few KiB can decompress to gigabytes, so reading stops right after the limit
+pub fn decompress(bytes: &[u8], limits: &Limits) -> Result<Vec<u8>, GraynessError> {
+    let mut svg_bytes = Vec::new();
+    GzDecoder::new(bytes)
+        .take(limits.max_bytes() + 1)
+        .read_to_end(&mut svg_bytes)
+        .map_err(|e| GraynessError::DecodeFailed(format!("Could not decompress SVGZ data: {e}")))?;
+    if svg_bytes.len() as u64 > limits.max_bytes() {
+        return Err(GraynessError::LimitExceeded(format!(
+            "The decompressed SVGZ data exceeds the memory limit of {} MiB, see the `max_memory` limit",
+            limits.max_memory
+        )));
+    }
+    Ok(svg_bytes)
+}
+
+//whether the root element is an `<svg>`, skipping any comments, processing instructions and doctype before it
+pub fn is_svg(bytes: &[u8]) -> bool {
+    let mut reader = EventReader::new(bytes);
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::StartElement { name, .. }) => return name.local_name == "svg",
+            Ok(XmlEvent::EndDocument) | Err(_) => return false,
+            _ => {}
+        }
+    }
+}
+
+//checks the limits without building the tree, before the SVG is handed to a recursive parser
+pub fn check_limits(bytes: &[u8], limits: &Limits) -> Result<(), GraynessError> {
+    let mut reader = EventReader::new(bytes);
+    let (mut depth, mut elements) = (0, 0);
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::StartElement { .. }) => {
+                depth += 1;
+                limits.check_element(depth, &mut elements)?;
+            }
+            Ok(XmlEvent::EndElement { .. }) => depth -= 1,
+            //syntax errors are left to the parser, which reports them in its own words
+            Ok(XmlEvent::EndDocument) | Err(_) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+//the message of xml-rs starts with the position, which is kept separately instead
+fn parse_error(e: xml::reader::Error) -> GraynessError {
+    let position = e.position();
+    let message = e.to_string();
+    GraynessError::SvgParse {
+        line: position.row + 1,
+        col: position.column + 1,
+        message: message
+            .strip_prefix(&format!("{position} "))
+            .unwrap_or(&message)
+            .to_string(),
+    }
+}
+
+//only comments and processing instructions can occur outside the root element
+fn write_nodes(out: &mut String, nodes: &[XMLNode]) {
+    for node in nodes {
+        match node {
+            XMLNode::Comment(comment) => {
+                let _ = write!(out, "<!--{comment}-->");
+            }
+            XMLNode::ProcessingInstruction(name, Some(data)) => {
+                let _ = write!(out, "<?{name} {data}?>");
+            }
+            XMLNode::ProcessingInstruction(name, None) => {
+                let _ = write!(out, "<?{name}?>");
+            }
+            _ => {}
+        }
+    }
+}
+
+fn new_element(
+    name: xml::name::OwnedName,
+    attributes: Vec<xml::attribute::OwnedAttribute>,
+    namespace: xml::namespace::Namespace,
+) -> Element {
+    let mut elem = Element::new(&name.local_name);
+    elem.prefix = name.prefix;
+    elem.namespace = name.namespace;
+    if !namespace.is_essentially_empty() {
+        elem.namespaces = Some(namespace);
+    }
+    //xmltree writes attribute names verbatim, so keeping the prefix in the name is enough to preserve it
+    for attr in attributes {
+        let key = match attr.name.prefix {
+            Some(prefix) => format!("{prefix}:{}", attr.name.local_name),
+            None => attr.name.local_name,
+        };
+        elem.attributes.insert(key, attr.value);
+    }
+    elem
+}
+
+//the tree is built recursively, so the depth is limited before it can overflow the stack
+fn build(
+    reader: &mut EventReader<&[u8]>,
+    mut elem: Element,
+    depth: usize,
+    elements: &mut usize,
+    limits: &Limits,
+) -> Result<Element, GraynessError> {
+    loop {
+        let event = reader.next().map_err(parse_error)?;
+        match event {
+            XmlEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            } => {
+                limits.check_element(depth + 1, elements)?;
+                let child = new_element(name, attributes, namespace);
+                let child = build(reader, child, depth + 1, elements, limits)?;
+                elem.children.push(XMLNode::Element(child));
+            }
+            XmlEvent::EndElement { .. } => return Ok(elem),
+            //whitespace is significant in text elements, so it is kept everywhere
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => {
+                elem.children.push(XMLNode::Text(text));
+            }
+            XmlEvent::CData(data) => elem.children.push(XMLNode::CData(data)),
+            XmlEvent::Comment(comment) => elem.children.push(XMLNode::Comment(comment)),
+            XmlEvent::ProcessingInstruction { name, data } => {
+                elem.children
+                    .push(XMLNode::ProcessingInstruction(name, data));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use xmltree::{Element, XMLNode};
+
+use super::options::Region;
+
+//builders for single filter primitives, see https://developer.mozilla.org/en-US/docs/Web/SVG/Element/filter
+
+pub fn grayscale() -> Element {
+    let mut colormatrix_elem = Element::new("feColorMatrix");
+    colormatrix_elem
+        .attributes
+        .insert("type".into(), "saturate".into());
+    colormatrix_elem.attributes.insert(
+        "values".into(),
+        "0.0".into(), //see https://developer.mozilla.org/en-US/docs/Web/SVG/Element/feColorMatrix
+    );
+    colormatrix_elem
+}
+
+pub fn blur(sigma: f32) -> Element {
+    let mut fe_gaussian_blur = Element::new("feGaussianBlur");
+    fe_gaussian_blur
+        .attributes
+        .insert("stdDeviation".into(), format!("{sigma}"));
+    fe_gaussian_blur
+}
+
+pub fn transparency(alpha: f32) -> Element {
+    //component transfer for the alpha channel
+    let mut fe_component_transfer = Element::new("feComponentTransfer");
+    let mut fe_func_a = Element::new("feFuncA");
+    fe_func_a.attributes.insert("type".into(), "linear".into());
+    fe_func_a
+        .attributes
+        .insert("slope".into(), format!("{alpha}"));
+
+    fe_component_transfer
+        .children
+        .push(XMLNode::Element(fe_func_a));
+    fe_component_transfer
+}
+
+pub fn invert() -> Element {
+    //component transfer for the RGB channels with inversion table
+    let mut fe_component_transfer = Element::new("feComponentTransfer");
+    fe_component_transfer
+        .attributes
+        .insert("color-interpolation-filters".into(), "sRGB".into());
+    for name in ["feFuncR", "feFuncG", "feFuncB"] {
+        let mut fe_func = Element::new(name);
+        fe_func.attributes.insert("type".into(), "table".into());
+        fe_func
+            .attributes
+            .insert("tableValues".into(), "1 0".into());
+        fe_component_transfer
+            .children
+            .push(XMLNode::Element(fe_func));
+    }
+    fe_component_transfer
+}
+
+pub fn brighten(amount: f32) -> Element {
+    //component transfer for the RGB channels
+    let mut fe_component_transfer = Element::new("feComponentTransfer");
+    fe_component_transfer
+        .attributes
+        .insert("color-interpolation-filters".into(), "sRGB".into());
+    for name in ["feFuncR", "feFuncG", "feFuncB"] {
+        let mut fe_func = Element::new(name);
+        fe_func.attributes.insert("type".into(), "linear".into());
+        fe_func.attributes.insert("slope".into(), "1".into());
+        fe_func
+            .attributes
+            .insert("intercept".into(), format!("{amount}"));
+        fe_component_transfer
+            .children
+            .push(XMLNode::Element(fe_func));
+    }
+    fe_component_transfer
+}
+
+pub fn huerotate(amount: f32) -> Element {
+    let mut fe_color_matrix = Element::new("feColorMatrix");
+    fe_color_matrix
+        .attributes
+        .insert("type".into(), "hueRotate".into());
+    fe_color_matrix
+        .attributes
+        .insert("values".into(), format!("{amount}"));
+    fe_color_matrix
+}
+
+pub fn matrix(m: &[f32; 20]) -> Element {
+    let mut fe_color_matrix = Element::new("feColorMatrix");
+    fe_color_matrix
+        .attributes
+        .insert("type".into(), "matrix".into());
+    fe_color_matrix.attributes.insert(
+        "values".into(),
+        m.iter()
+            .map(|v| format!("{v}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    fe_color_matrix
+}
+
+//creates a filter element which applies the given primitives one after another,
+//covering the given region in user units instead of the bounding box, which is empty for horizontal or vertical lines
+pub fn build_filter(id: &str, primitives: Vec<Element>, region: Option<&Region>) -> Element {
+    let mut filter_elem = Element::new("filter");
+    filter_elem.attributes.insert("id".into(), id.to_string());
+    if let Some(region) = region {
+        let attributes = &mut filter_elem.attributes;
+        attributes.insert("filterUnits".into(), "userSpaceOnUse".into());
+        attributes.insert("x".into(), format!("{}", region.x));
+        attributes.insert("y".into(), format!("{}", region.y));
+        attributes.insert("width".into(), format!("{}", region.width));
+        attributes.insert("height".into(), format!("{}", region.height));
+    }
+
+    //chain the primitives explicitly, the first one implicitly takes the SourceGraphic as input
+    for (i, mut primitive) in primitives.into_iter().enumerate() {
+        if i > 0 {
+            primitive
+                .attributes
+                .insert("in".into(), format!("{id}_{}", i - 1));
+        }
+        primitive
+            .attributes
+            .insert("result".into(), format!("{id}_{i}"));
+        filter_elem.children.push(XMLNode::Element(primitive));
+    }
+
+    filter_elem
+}
@@ -0,0 +1,207 @@
+use xmltree::Element;
+
+use super::wrap_children;
+use crate::error::GraynessError;
+
+//size of one unit in user units (px), see https://www.w3.org/TR/css-values-3/#absolute-lengths
+fn unit_to_px(unit: &str) -> Option<f32> {
+    match unit {
+        "" | "px" => Some(1.0),
+        "in" => Some(96.0),
+        "cm" => Some(96.0 / 2.54),
+        "mm" => Some(96.0 / 25.4),
+        "pt" => Some(96.0 / 72.0),
+        "pc" => Some(16.0),
+        _ => None,
+    }
+}
+
+//splits a length like "12.5mm" into its number and unit
+pub fn parse_length(value: &str) -> Option<(f32, &str)> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    Some((number.parse().ok()?, unit.trim()))
+}
+
+//scales a length, keeping its unit, including relative ones like percentages
+pub fn scale_length(value: &str, factor: f32) -> String {
+    match parse_length(value) {
+        Some((number, unit)) => format!("{}{unit}", number * factor),
+        None => value.to_string(),
+    }
+}
+
+//a length in px, None for relative lengths
+fn length_px(value: &str) -> Option<f32> {
+    let (number, unit) = parse_length(value)?;
+    Some(number * unit_to_px(unit)?)
+}
+
+//the viewBox as (x, y, width, height), derived from the width and height if there is none
+pub fn viewbox(svg_elem: &Element) -> Result<(f32, f32, f32, f32), GraynessError> {
+    if let Some(viewbox) = svg_elem.attributes.get("viewBox") {
+        let values = viewbox
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|v| !v.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|e| GraynessError::InvalidSvg(format!("Could not parse SVG viewBox: {e}")))?;
+        if let [x, y, width, height] = values[..] {
+            return Ok((x, y, width, height));
+        }
+        return Err(GraynessError::InvalidSvg(format!(
+            "Invalid SVG viewBox: {viewbox}"
+        )));
+    }
+
+    let length = |name: &str| -> Result<f32, GraynessError> {
+        let value = svg_elem
+            .attributes
+            .get(name)
+            .ok_or(GraynessError::InvalidSvg(format!(
+                "SVG has neither a viewBox nor a {name}"
+            )))?;
+        length_px(value).ok_or(GraynessError::InvalidSvg(format!(
+            "Could not convert SVG {name} to user units: {value}"
+        )))
+    };
+    Ok((0.0, 0.0, length("width")?, length("height")?))
+}
+
+pub fn set_viewbox(svg_elem: &mut Element, start_x: f32, start_y: f32, width: f32, height: f32) {
+    svg_elem.attributes.insert(
+        "viewBox".to_string(),
+        format!("{start_x} {start_y} {width} {height}"),
+    );
+}
+
+//multiples of 90° are calculated exactly, so the new viewBox doesn't pick up rounding errors
+fn sin_cos(angle: f32) -> (f32, f32) {
+    let normalized = angle.rem_euclid(360.0);
+    if normalized == 0.0 {
+        (0.0, 1.0)
+    } else if normalized == 90.0 {
+        (1.0, 0.0)
+    } else if normalized == 180.0 {
+        (0.0, -1.0)
+    } else if normalized == 270.0 {
+        (-1.0, 0.0)
+    } else {
+        angle.to_radians().sin_cos()
+    }
+}
+
+//rotates clockwise by the given angle in degrees around the center, enlarging the viewBox to fit
+pub fn rotate(svg_elem: &mut Element, angle: f32) -> Result<(), GraynessError> {
+    let (x, y, width, height) = viewbox(svg_elem)?;
+    let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+    let (sin, cos) = sin_cos(angle);
+    let new_width = width * cos.abs() + height * sin.abs();
+    let new_height = width * sin.abs() + height * cos.abs();
+
+    let mut group = Element::new("g");
+    group
+        .attributes
+        .insert("transform".into(), format!("rotate({angle} {cx} {cy})"));
+    wrap_children(svg_elem, group);
+
+    set_viewbox(
+        svg_elem,
+        cx - new_width / 2.0,
+        cy - new_height / 2.0,
+        new_width,
+        new_height,
+    );
+    //percentages refer to the viewport, which stays the same, so only absolute sizes follow the viewBox
+    for (name, factor) in [
+        ("width", new_width / width),
+        ("height", new_height / height),
+    ] {
+        if let Some(value) = svg_elem.attributes.get_mut(name)
+            && length_px(value).is_some()
+        {
+            *value = scale_length(value, factor);
+        }
+    }
+    Ok(())
+}
+
+//mirrors the content within the viewBox, horizontally or vertically
+pub fn flip(svg_elem: &mut Element, horizontal: bool) -> Result<(), GraynessError> {
+    let (x, y, width, height) = viewbox(svg_elem)?;
+    let transform = if horizontal {
+        format!("matrix(-1 0 0 1 {} 0)", 2.0 * x + width)
+    } else {
+        format!("matrix(1 0 0 -1 0 {})", 2.0 * y + height)
+    };
+
+    let mut group = Element::new("g");
+    group.attributes.insert("transform".into(), transform);
+    wrap_children(svg_elem, group);
+
+    set_viewbox(svg_elem, x, y, width, height);
+    Ok(())
+}
+
+fn scale_size(svg_elem: &mut Element, x_factor: f32, y_factor: f32) {
+    if let Some(width) = svg_elem.attributes.get_mut("width") {
+        *width = scale_length(width, x_factor);
+    }
+    if let Some(height) = svg_elem.attributes.get_mut("height") {
+        *height = scale_length(height, y_factor);
+    }
+}
+
+//the size of one user unit in px along each axis, as determined by the width, height and preserveAspectRatio
+fn viewport_scale(
+    svg_elem: &Element,
+    viewbox_width: f32,
+    viewbox_height: f32,
+) -> Option<(f32, f32)> {
+    let width = length_px(svg_elem.attributes.get("width")?)?;
+    let height = length_px(svg_elem.attributes.get("height")?)?;
+    let (x_scale, y_scale) = (width / viewbox_width, height / viewbox_height);
+
+    let aspect_ratio = svg_elem
+        .attributes
+        .get("preserveAspectRatio")
+        .map_or("xMidYMid meet", String::as_str);
+    if aspect_ratio.split_whitespace().next() == Some("none") {
+        Some((x_scale, y_scale))
+    } else if aspect_ratio.contains("slice") {
+        Some((x_scale.max(y_scale), x_scale.max(y_scale)))
+    } else {
+        Some((x_scale.min(y_scale), x_scale.min(y_scale)))
+    }
+}
+
+//sets a length attribute to the given size in px, keeping its unit
+fn set_length_px(svg_elem: &mut Element, name: &str, px: f32) {
+    if let Some(value) = svg_elem.attributes.get_mut(name)
+        && let Some((_, unit)) = parse_length(value)
+        && let Some(unit_px) = unit_to_px(unit)
+    {
+        //unit conversions leave small rounding errors like 20.000002pt
+        let number = (px / unit_px * 10000.0).round() / 10000.0;
+        *value = format!("{number}{unit}");
+    }
+}
+
+//crops to the given region of user space, shrinking the width and height so the content keeps its size
+pub fn crop(svg_elem: &mut Element, x: f32, y: f32, width: f32, height: f32) {
+    if let Ok((_, _, old_width, old_height)) = viewbox(svg_elem) {
+        //with absolute sizes, any letterboxing from preserveAspectRatio is cropped as well,
+        //relative sizes can only be scaled proportionally
+        match viewport_scale(svg_elem, old_width, old_height) {
+            Some((x_scale, y_scale)) => {
+                set_length_px(svg_elem, "width", width * x_scale);
+                set_length_px(svg_elem, "height", height * y_scale);
+            }
+            None => scale_size(svg_elem, width / old_width, height / old_height),
+        }
+    }
+    set_viewbox(svg_elem, x, y, width, height);
+}
@@ -1,338 +1,398 @@
-use wasm_minimal_protocol::wasm_func;
+use base64::prelude::*;
+use image::ImageFormat;
 use xmltree::{Element, XMLNode};
 
-use crate::__BytesOrResultBytes;
-use crate::__send_result_to_host;
-use crate::__write_args_to_buffer;
+#[cfg(target_arch = "wasm32")]
+use crate::{__BytesOrResultBytes, __send_result_to_host, __write_args_to_buffer};
+#[cfg(target_arch = "wasm32")]
+use wasm_minimal_protocol::wasm_func;
+
+pub mod document;
+mod filters;
+mod geometry;
+pub mod options;
+pub mod pipeline;
+pub mod rasterize;
+mod recolor;
+
+use crate::error::GraynessError;
+use crate::limits::Limits;
+use crate::{args, raster};
+use document::Document;
+pub(crate) use document::{decompress, is_gzip, is_svg};
+use options::{Mode, Options, Region};
+use pipeline::{
+    AmountArgs, BlurArgs, CropArgs, MaskArgs, MatrixArgs, Operation, RotateArgs, TransparencyArgs,
+};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
 
 static TYPST_FILTER_ID_PREFIX: &str = "Typst_Filter_ID_";
+static TYPST_MASK_ID_PREFIX: &str = "Typst_Mask_ID_";
+static TYPST_CLIP_ID_PREFIX: &str = "Typst_Clip_ID_";
 
-fn get_next_filter_index(root: &Element) -> usize {
+//the attribute is `filter`, `mask` or `clip-path`, with the matching ID prefix
+fn get_next_index(elem: &Element, attribute: &str, id_prefix: &str) -> usize {
     let mut max_n = 0;
 
-    //look through every g element with a filter attribute matching the specified format and extract the maximum ID
-    for child in &root.children {
-        let XMLNode::Element(elem) = child else {
-            continue;
-        };
+    //look through every element with an attribute matching the specified format and extract the maximum ID,
+    //descending into children since previous filters may have been wrapped by transforming groups
+    let prefix = format!("url(#{id_prefix}");
+    let suffix = ")";
+    if let Some(id) = elem.attributes.get(attribute)
+        && let Some(rest) = id.strip_prefix(&prefix)
+        && let Some(num) = rest.strip_suffix(suffix)
+        && let Ok(n) = num.parse::<usize>()
+    {
+        max_n = n;
+    }
 
-        if elem.name != "g" {
-            continue;
-        }
-        let prefix = format!("url(#{TYPST_FILTER_ID_PREFIX}");
-        let suffix = ")";
-        if let Some(id) = elem.attributes.get("filter")
-            && let Some(rest) = id.strip_prefix(&prefix)
-            && let Some(num) = rest.strip_suffix(suffix)
-            && let Ok(n) = num.parse::<usize>()
-        {
-            max_n = max_n.max(n);
+    for child in &elem.children {
+        if let XMLNode::Element(child) = child {
+            max_n = max_n.max(get_next_index(child, attribute, id_prefix) - 1);
         }
     }
 
     max_n + 1
 }
 
-fn add_svg_filter(
-    mut svg_elem: Element,
-    id: &str,
-    filter_elem: Element,
-) -> Result<Vec<u8>, String> {
-    //wrap all existing elements in a new group with the filter applied
-    let mut group_element = Element::new("g");
-    group_element
-        .attributes
-        .insert("filter".into(), format!("url(#{id})"));
-
-    for child in svg_elem.children {
-        if let XMLNode::Element(elem) = child {
-            group_element.children.push(XMLNode::Element(elem));
+//elements which aren't rendered themselves and stay outside of any group wrapping the content
+const NON_RENDERED_ELEMENTS: [&str; 6] = ["title", "desc", "metadata", "defs", "style", "script"];
+
+//moves the content into the given group, which follows the non-rendered elements as the last child
+fn wrap_children(svg_elem: &mut Element, mut group_element: Element) {
+    let mut kept = Vec::new();
+    for child in std::mem::take(&mut svg_elem.children) {
+        match child {
+            XMLNode::Element(ref elem) if NON_RENDERED_ELEMENTS.contains(&elem.name.as_str()) => {
+                kept.push(child);
+            }
+            _ => group_element.children.push(child),
         }
     }
+    kept.push(XMLNode::Element(group_element));
+    svg_elem.children = kept;
+}
 
-    //add filter and replace existing children with new group
-    svg_elem.children = vec![
-        XMLNode::Element(filter_elem),
-        XMLNode::Element(group_element),
-    ];
+//adds an element to the first `<defs>` child of the root, creating one if necessary
+fn add_def(svg_elem: &mut Element, def: Element) {
+    if let Some(defs) = svg_elem.get_mut_child("defs") {
+        defs.children.push(XMLNode::Element(def));
+    } else {
+        let mut defs = Element::new("defs");
+        defs.children.push(XMLNode::Element(def));
+        svg_elem.children.insert(0, XMLNode::Element(defs));
+    }
+}
 
-    let mut svg_output = Vec::new();
+//the region a filter applied to the whole content may draw to: the viewBox, enlarged by three standard deviations
+//for every blur so it doesn't get clipped, unless the caller overrides it
+fn filter_region(
+    svg_elem: &Element,
+    operations: &[&Operation],
+    options: &Options,
+) -> Option<Region> {
+    if let Some(region) = &options.region {
+        return Some(region.clone());
+    }
+    let (x, y, width, height) = geometry::viewbox(svg_elem).ok()?;
+    let margin: f32 = operations
+        .iter()
+        .map(|op| match op {
+            Operation::Blur(a) => 3.0 * a.sigma,
+            _ => 0.0,
+        })
+        .sum();
+    Some(Region {
+        x: x - margin,
+        y: y - margin,
+        width: width + 2.0 * margin,
+        height: height + 2.0 * margin,
+    })
+}
 
-    svg_elem
-        .write(&mut svg_output)
-        .map_err(|e| format!("Could not write SVG bytes: {e:?}"))?;
-    Ok(svg_output)
+//the mask as a data URI, which may be another SVG or any supported raster image
+fn mask_data_uri(mask_bytes: &[u8], limits: &Limits) -> Result<String, GraynessError> {
+    let mime_type = match image::guess_format(mask_bytes) {
+        Ok(format) => format.to_mime_type(),
+        Err(_) if Document::parse(mask_bytes, limits).is_ok() => "image/svg+xml",
+        Err(_) => {
+            return Err(GraynessError::UnsupportedFormat(
+                "Unknown mask image format".to_string(),
+            ));
+        }
+    };
+    Ok(format!(
+        "data:{mime_type};base64,{}",
+        BASE64_STANDARD.encode(mask_bytes)
+    ))
 }
 
-#[wasm_func]
-fn svg_grayscale(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
-    let num = get_next_filter_index(&svg_elem);
+//masks the content with the given image stretched over the viewBox, like the raster mask is resized to the image
+fn add_svg_mask(
+    svg_elem: &mut Element,
+    mask_bytes: &[u8],
+    use_alpha: bool,
+    limits: &Limits,
+) -> Result<(), GraynessError> {
+    let (x, y, width, height) = geometry::viewbox(svg_elem)?;
+    let num = get_next_index(svg_elem, "mask", TYPST_MASK_ID_PREFIX);
+    let id = format!("{TYPST_MASK_ID_PREFIX}{num}");
+
+    let mut image_elem = Element::new("image");
+    let mut mask_elem = Element::new("mask");
+    for elem in [&mut image_elem, &mut mask_elem] {
+        elem.attributes.insert("x".into(), format!("{x}"));
+        elem.attributes.insert("y".into(), format!("{y}"));
+        elem.attributes.insert("width".into(), format!("{width}"));
+        elem.attributes.insert("height".into(), format!("{height}"));
+    }
+    image_elem
+        .attributes
+        .insert("preserveAspectRatio".into(), "none".into());
+    image_elem
+        .attributes
+        .insert("href".into(), mask_data_uri(mask_bytes, limits)?);
 
-    //create a filter element with a colormatrix
-    let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    let mut colormatrix_elem = Element::new("feColorMatrix");
-    colormatrix_elem
+    mask_elem.attributes.insert("id".into(), id.clone());
+    mask_elem
+        .attributes
+        .insert("maskUnits".into(), "userSpaceOnUse".into());
+    let mask_type = if use_alpha { "alpha" } else { "luminance" };
+    mask_elem
+        .attributes
+        .insert("mask-type".into(), mask_type.into());
+    mask_elem.children.push(XMLNode::Element(image_elem));
+
+    let mut group_element = Element::new("g");
+    group_element
         .attributes
-        .insert("type".into(), "saturate".into());
-    colormatrix_elem.attributes.insert(
-        "values".into(),
-        "0.0".into(), //see https://developer.mozilla.org/en-US/docs/Web/SVG/Element/feColorMatrix
-    );
-    filter_elem
-        .children
-        .push(XMLNode::Element(colormatrix_elem));
+        .insert("mask".into(), format!("url(#{id})"));
+    wrap_children(svg_elem, group_element);
 
-    add_svg_filter(svg_elem, &id, filter_elem)
+    add_def(svg_elem, mask_elem);
+    Ok(())
 }
 
-#[wasm_func]
-fn svg_crop(
-    image_bytes: &[u8],
-    start_x: &[u8],
-    start_y: &[u8],
-    width: &[u8],
-    height: &[u8],
-) -> Result<Vec<u8>, String> {
-    let start_x = f32::from_le_bytes(
-        start_x
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let start_y = f32::from_le_bytes(
-        start_y
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let width = f32::from_le_bytes(
-        width
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let height = f32::from_le_bytes(
-        height
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let mut svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
-    if svg_elem.attributes.contains_key("viewBox") {
-        *svg_elem.attributes.get_mut("viewBox").unwrap() =
-            format!("{start_x} {start_y} {width} {height}");
-    } else {
-        svg_elem.attributes.insert(
-            "viewBox".to_string(),
-            format!("{start_x} {start_y} {width} {height}"),
-        );
-    }
+//clips the content to a rectangle in user space
+fn add_svg_clip(svg_elem: &mut Element, x: f32, y: f32, width: f32, height: f32) {
+    let num = get_next_index(svg_elem, "clip-path", TYPST_CLIP_ID_PREFIX);
+    let id = format!("{TYPST_CLIP_ID_PREFIX}{num}");
 
-    let mut svg_output = Vec::new();
-    svg_elem
-        .write(&mut svg_output)
-        .map_err(|e| format!("Could not write SVG bytes: {e:?}"))?;
-    Ok(svg_output)
-}
+    let mut rect_elem = Element::new("rect");
+    rect_elem.attributes.insert("x".into(), format!("{x}"));
+    rect_elem.attributes.insert("y".into(), format!("{y}"));
+    rect_elem
+        .attributes
+        .insert("width".into(), format!("{width}"));
+    rect_elem
+        .attributes
+        .insert("height".into(), format!("{height}"));
+    let mut clip_elem = Element::new("clipPath");
+    clip_elem.attributes.insert("id".into(), id.clone());
+    clip_elem.children.push(XMLNode::Element(rect_elem));
 
-#[wasm_func]
-fn svg_blur(image_bytes: &[u8], sigma: &[u8]) -> Result<Vec<u8>, String> {
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
+    let mut group_element = Element::new("g");
+    group_element
+        .attributes
+        .insert("clip-path".into(), format!("url(#{id})"));
+    wrap_children(svg_elem, group_element);
 
-    let sigma = f32::from_le_bytes(
-        sigma
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
+    add_def(svg_elem, clip_elem);
+}
 
-    let num = get_next_filter_index(&svg_elem);
-    //create a gaussian blur filter
+fn add_svg_filter(svg_elem: &mut Element, primitives: Vec<Element>, region: Option<&Region>) {
+    let num = get_next_index(svg_elem, "filter", TYPST_FILTER_ID_PREFIX);
     let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    let mut fe_gaussian_blur = Element::new("feGaussianBlur");
-    fe_gaussian_blur
-        .attributes
-        .insert("stdDeviation".into(), format!("{sigma}"));
+    let filter_elem = filters::build_filter(&id, primitives, region);
 
-    filter_elem
-        .children
-        .push(XMLNode::Element(fe_gaussian_blur));
+    //wrap all existing elements in a new group with the filter applied
+    let mut group_element = Element::new("g");
+    group_element
+        .attributes
+        .insert("filter".into(), format!("url(#{id})"));
+    wrap_children(svg_elem, group_element);
 
-    add_svg_filter(svg_elem, &id, filter_elem)
+    add_def(svg_elem, filter_elem);
 }
 
-#[wasm_func]
-fn svg_transparency(image_bytes: &[u8], alpha: &[u8]) -> Result<Vec<u8>, String> {
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
+//embedded raster images can't be recolored, so only they get the filter, wrapped in a group to keep any filter of their own
+fn filter_images(elem: &mut Element, id: &str) -> bool {
+    let mut found = false;
+    for child in &mut elem.children {
+        let XMLNode::Element(child_elem) = child else {
+            continue;
+        };
+        if child_elem.name == "image" {
+            let mut group_element = Element::new("g");
+            group_element
+                .attributes
+                .insert("filter".into(), format!("url(#{id})"));
+            let image = std::mem::replace(child, XMLNode::Element(group_element));
+            if let XMLNode::Element(group_element) = child {
+                group_element.children.push(image);
+            }
+            found = true;
+        } else {
+            found |= filter_images(child_elem, id);
+        }
+    }
+    found
+}
 
-    let alpha = f32::from_le_bytes(
-        alpha
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
+fn recolor_svg(svg_elem: &mut Element, operations: &[&Operation]) {
+    recolor::recolor(svg_elem, &|c| {
+        operations.iter().fold(c, |c, op| op.recolor(c))
+    });
 
-    let num = get_next_filter_index(&svg_elem);
-    //create a component transfer filter for the alpha channel
+    let num = get_next_index(svg_elem, "filter", TYPST_FILTER_ID_PREFIX);
     let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    let mut fe_component_transfer = Element::new("feComponentTransfer");
-    let mut fe_func_a = Element::new("feFuncA");
-    fe_func_a.attributes.insert("type".into(), "linear".into());
-    fe_func_a
-        .attributes
-        .insert("slope".into(), format!("{alpha}"));
-
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_a));
-
-    filter_elem
-        .children
-        .push(XMLNode::Element(fe_component_transfer));
+    if filter_images(svg_elem, &id) {
+        //the images may be transformed in any way, so the default region relative to their bounding box is kept
+        let primitives = operations.iter().filter_map(|op| op.primitive()).collect();
+        let filter_elem = filters::build_filter(&id, primitives, None);
+        add_def(svg_elem, filter_elem);
+    }
+}
 
-    add_svg_filter(svg_elem, &id, filter_elem)
+//applies a run of consecutive color operations, either as a single filter wrapping the content or by recoloring it
+fn apply_colors(
+    svg_elem: &mut Element,
+    color_ops: &[&Operation],
+    options: &Options,
+) -> Result<(), GraynessError> {
+    if color_ops.is_empty() {
+        return Ok(());
+    }
+    match options.mode {
+        //all filter primitives are combined into a single filter, so the content is only wrapped once
+        Mode::Filter => {
+            let region = filter_region(svg_elem, color_ops, options);
+            let primitives = color_ops.iter().filter_map(|op| op.primitive()).collect();
+            add_svg_filter(svg_elem, primitives, region.as_ref());
+        }
+        Mode::Recolor => {
+            if color_ops.iter().any(|op| matches!(op, Operation::Blur(_))) {
+                return Err(GraynessError::invalid_argument(
+                    "mode",
+                    "blurring requires a filter and can't be used in recolor mode",
+                ));
+            }
+            recolor_svg(svg_elem, color_ops);
+        }
+    }
+    Ok(())
 }
 
-#[wasm_func]
-fn svg_invert(image_bytes: &[u8]) -> Result<Vec<u8>, String> {
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
+//applies the operations to the root element of an SVG, without going through its bytes.
+//every structure operation wraps the content in a new group, so the color operations before it are applied first
+pub fn apply(
+    svg_elem: &mut Element,
+    operations: &[Operation],
+    options: &Options,
+) -> Result<(), GraynessError> {
+    let mut color_ops = Vec::new();
+    for op in operations {
+        if op.primitive().is_some() {
+            color_ops.push(op);
+        } else {
+            apply_colors(svg_elem, &color_ops, options)?;
+            color_ops.clear();
+            op.apply_structure(svg_elem, &options.limits)?;
+        }
+    }
+    apply_colors(svg_elem, &color_ops, options)
+}
 
-    let num = get_next_filter_index(&svg_elem);
-    //create a component transfer filter for the RGB channels with inversion table
-    let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    filter_elem
-        .attributes
-        .insert("style".into(), "color-interpolation-filters:sRGB".into());
-    let mut fe_component_transfer = Element::new("feComponentTransfer");
-    let mut fe_func_r = Element::new("feFuncR");
-    let mut fe_func_g = Element::new("feFuncG");
-    let mut fe_func_b = Element::new("feFuncB");
-    fe_func_r.attributes.insert("type".into(), "table".into());
-    fe_func_r
-        .attributes
-        .insert("tableValues".into(), "1 0".into());
-    fe_func_g.attributes.insert("type".into(), "table".into());
-    fe_func_g
-        .attributes
-        .insert("tableValues".into(), "1 0".into());
-    fe_func_b.attributes.insert("type".into(), "table".into());
-    fe_func_b
-        .attributes
-        .insert("tableValues".into(), "1 0".into());
+pub fn apply_operations(
+    image_bytes: &[u8],
+    operations: &[Operation],
+    options: &Options,
+) -> Result<Vec<u8>, GraynessError> {
+    let mut document = Document::parse(image_bytes, &options.limits)?;
+    if let Some(compress) = options.compress {
+        document.compressed = compress;
+    }
+    apply(&mut document.root, operations, options)?;
+    document.write()
+}
 
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_r));
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_g));
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_b));
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_grayscale(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Grayscale], &Options::default())
+}
 
-    filter_elem
-        .children
-        .push(XMLNode::Element(fe_component_transfer));
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_mask(
+    image_bytes: &[u8],
+    mask_image_bytes: &[u8],
+    use_alpha: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Mask(MaskArgs {
+        mask: mask_image_bytes.to_vec(),
+        use_alpha: args::bool_arg(use_alpha),
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    add_svg_filter(svg_elem, &id, filter_elem)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_crop(
+    image_bytes: &[u8],
+    start_x: &[u8],
+    start_y: &[u8],
+    width: &[u8],
+    height: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Crop(CropArgs {
+        x: args::f32_arg(start_x, "start_x")?,
+        y: args::f32_arg(start_y, "start_y")?,
+        width: args::f32_arg(width, "width")?,
+        height: args::f32_arg(height, "height")?,
+        clip: false,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
 }
 
-#[wasm_func]
-fn svg_brighten(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, String> {
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_blur(image_bytes: &[u8], sigma: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Blur(BlurArgs {
+        sigma: args::f32_arg(sigma, "sigma")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    let amount = f32::from_le_bytes(
-        amount
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_transparency(image_bytes: &[u8], alpha: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Transparency(TransparencyArgs {
+        alpha: args::f32_arg(alpha, "alpha")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    let num = get_next_filter_index(&svg_elem);
-    let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    //create a component transfer filter for the RGB channels
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    filter_elem
-        .attributes
-        .insert("style".into(), "color-interpolation-filters:sRGB".into());
-    let mut fe_component_transfer = Element::new("feComponentTransfer");
-    let mut fe_func_r = Element::new("feFuncR");
-    let mut fe_func_g = Element::new("feFuncG");
-    let mut fe_func_b = Element::new("feFuncB");
-    fe_func_r.attributes.insert("type".into(), "linear".into());
-    fe_func_r.attributes.insert("slope".into(), "1".into());
-    fe_func_r
-        .attributes
-        .insert("intercept".into(), format!("{amount}"));
-    fe_func_g.attributes.insert("type".into(), "linear".into());
-    fe_func_g.attributes.insert("slope".into(), "1".into());
-    fe_func_g
-        .attributes
-        .insert("intercept".into(), format!("{amount}"));
-    fe_func_b.attributes.insert("type".into(), "linear".into());
-    fe_func_b.attributes.insert("slope".into(), "1".into());
-    fe_func_b
-        .attributes
-        .insert("intercept".into(), format!("{amount}"));
-
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_r));
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_g));
-    fe_component_transfer
-        .children
-        .push(XMLNode::Element(fe_func_b));
-
-    filter_elem
-        .children
-        .push(XMLNode::Element(fe_component_transfer));
-
-    add_svg_filter(svg_elem, &id, filter_elem)
-}
-
-#[wasm_func]
-fn svg_huerotate(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, String> {
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
-
-    let amount = f32::from_le_bytes(
-        amount
-            .try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-
-    let num = get_next_filter_index(&svg_elem);
-    //create a Hue-rotating filter
-    let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    let mut fe_color_matrix = Element::new("feColorMatrix");
-    fe_color_matrix
-        .attributes
-        .insert("type".into(), "hueRotate".into());
-    fe_color_matrix
-        .attributes
-        .insert("values".into(), format!("{amount}"));
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_invert(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Invert], &Options::default())
+}
 
-    filter_elem.children.push(XMLNode::Element(fe_color_matrix));
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_brighten(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Brighten(AmountArgs {
+        amount: args::f32_arg(amount, "amount")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    add_svg_filter(svg_elem, &id, filter_elem)
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_huerotate(image_bytes: &[u8], amount: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Huerotate(AmountArgs {
+        amount: args::f32_arg(amount, "amount")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
 }
 
-#[wasm_func]
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
 #[allow(clippy::too_many_arguments)]
-fn svg_matrix(
+pub fn svg_matrix(
     image_bytes: &[u8],
     m00: &[u8],
     m01: &[u8],
@@ -354,105 +414,183 @@ fn svg_matrix(
     m32: &[u8],
     m33: &[u8],
     m34: &[u8],
-) -> Result<Vec<u8>, String> {
-    let m00 = f32::from_le_bytes(
-        m00.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m01 = f32::from_le_bytes(
-        m01.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m02 = f32::from_le_bytes(
-        m02.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m03 = f32::from_le_bytes(
-        m03.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m04 = f32::from_le_bytes(
-        m04.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m10 = f32::from_le_bytes(
-        m10.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m11 = f32::from_le_bytes(
-        m11.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m12 = f32::from_le_bytes(
-        m12.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m13 = f32::from_le_bytes(
-        m13.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m14 = f32::from_le_bytes(
-        m14.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m20 = f32::from_le_bytes(
-        m20.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m21 = f32::from_le_bytes(
-        m21.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m22 = f32::from_le_bytes(
-        m22.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m23 = f32::from_le_bytes(
-        m23.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m24 = f32::from_le_bytes(
-        m24.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m30 = f32::from_le_bytes(
-        m30.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m31 = f32::from_le_bytes(
-        m31.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m32 = f32::from_le_bytes(
-        m32.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m33 = f32::from_le_bytes(
-        m33.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-    let m34 = f32::from_le_bytes(
-        m34.try_into()
-            .map_err(|e| format!("could not convert bytes to float: {e:?}"))?,
-    );
-
-    let svg_elem =
-        Element::parse(image_bytes).map_err(|e| format!("Could not parse SVG data: {e:?}"))?;
-
-    let num = get_next_filter_index(&svg_elem);
-    //create a Hue-rotating filter
-    let id = format!("{TYPST_FILTER_ID_PREFIX}{num}");
-    let mut filter_elem = Element::new("filter");
-    filter_elem.attributes.insert("id".into(), id.clone());
-    let mut fe_color_matrix = Element::new("feColorMatrix");
-    fe_color_matrix
-        .attributes
-        .insert("type".into(), "matrix".into());
-    fe_color_matrix
-        .attributes
-        .insert("values".into(), format!("{m00} {m01} {m02} {m03} {m04} {m10} {m11} {m12} {m13} {m14} {m20} {m21} {m22} {m23} {m24} {m30} {m31} {m32} {m33} {m34}"));
+) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Matrix(MatrixArgs {
+        matrix: [
+            args::f32_arg(m00, "m00")?,
+            args::f32_arg(m01, "m01")?,
+            args::f32_arg(m02, "m02")?,
+            args::f32_arg(m03, "m03")?,
+            args::f32_arg(m04, "m04")?,
+            args::f32_arg(m10, "m10")?,
+            args::f32_arg(m11, "m11")?,
+            args::f32_arg(m12, "m12")?,
+            args::f32_arg(m13, "m13")?,
+            args::f32_arg(m14, "m14")?,
+            args::f32_arg(m20, "m20")?,
+            args::f32_arg(m21, "m21")?,
+            args::f32_arg(m22, "m22")?,
+            args::f32_arg(m23, "m23")?,
+            args::f32_arg(m24, "m24")?,
+            args::f32_arg(m30, "m30")?,
+            args::f32_arg(m31, "m31")?,
+            args::f32_arg(m32, "m32")?,
+            args::f32_arg(m33, "m33")?,
+            args::f32_arg(m34, "m34")?,
+        ],
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
 
-    filter_elem.children.push(XMLNode::Element(fe_color_matrix));
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rotate90(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Rotate90], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rotate180(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Rotate180], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rotate270(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Rotate270], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_fliph(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Fliph], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_flipv(image_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_operations(image_bytes, &[Operation::Flipv], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rotate(image_bytes: &[u8], angle: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let op = Operation::Rotate(RotateArgs {
+        angle: args::f32_arg(angle, "angle")?,
+    });
+    apply_operations(image_bytes, &[op], &Options::default())
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_pipeline(image_bytes: &[u8], ops_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let operations: Vec<Operation> = args::decode(ops_bytes)?;
+    apply_operations(image_bytes, &operations, &Options::default())
+}
+
+//renders the SVG to a PNG, e.g. to process it further with the raster functions
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rasterize(
+    image_bytes: &[u8],
+    width: &[u8],
+    height: &[u8],
+    background: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    let img = rasterize::rasterize(
+        image_bytes,
+        args::u32_arg(width, "width")?,
+        args::u32_arg(height, "height")?,
+        args::rgba_arg(background, "background")?,
+        &Limits::default(),
+    )?;
+    raster::write_image_buffer(
+        &img.into(),
+        ImageFormat::Png,
+        &raster::options::Options::default(),
+    )
+}
+
+fn apply_cbor<T: DeserializeOwned>(
+    image_bytes: &[u8],
+    args_bytes: &[u8],
+    operation: fn(T) -> Operation,
+) -> Result<Vec<u8>, GraynessError> {
+    let op = operation(args::decode(args_bytes)?);
+    let options: Options = args::decode(args_bytes)?;
+    apply_operations(image_bytes, &[op], &options)
+}
+
+#[derive(Deserialize)]
+struct PipelineArgs {
+    operations: Vec<Operation>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_pipeline_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let pipeline: PipelineArgs = args::decode(args_bytes)?;
+    let options: Options = args::decode(args_bytes)?;
+    apply_operations(image_bytes, &pipeline.operations, &options)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_mask_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Mask)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_crop_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Crop)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_blur_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Blur)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_transparency_cbor(
+    image_bytes: &[u8],
+    args_bytes: &[u8],
+) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Transparency)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_brighten_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Brighten)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_huerotate_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Huerotate)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_matrix_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Matrix)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rotate_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    apply_cbor(image_bytes, args_bytes, Operation::Rotate)
+}
+
+#[derive(Deserialize)]
+struct RasterizeArgs {
+    //0 or missing to derive the size from the SVG
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    //RGBA, transparent by default
+    #[serde(default)]
+    background: [u8; 4],
+}
 
-    add_svg_filter(svg_elem, &id, filter_elem)
+//the raster options like `compression` or `format` configure the encoding of the rendered image
+#[cfg_attr(target_arch = "wasm32", wasm_func)]
+pub fn svg_rasterize_cbor(image_bytes: &[u8], args_bytes: &[u8]) -> Result<Vec<u8>, GraynessError> {
+    let a: RasterizeArgs = args::decode(args_bytes)?;
+    let options: raster::options::Options = args::decode(args_bytes)?;
+    let img = rasterize::rasterize(
+        image_bytes,
+        a.width,
+        a.height,
+        a.background,
+        &options.limits,
+    )?;
+    raster::write_image_buffer(&img.into(), ImageFormat::Png, &options)
 }
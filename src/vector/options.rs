@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+use crate::args;
+use crate::limits::Limits;
+
+//settings concerning the whole SVG rather than a single operation, given alongside the arguments of the `_cbor` functions
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    pub mode: Mode,
+    //the region of the filter in user units, replacing the one derived from the viewBox
+    pub region: Option<Region>,
+    //whether to gzip the output as SVGZ, keeping the compression of the input if not given
+    pub compress: Option<bool>,
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Region {
+    #[serde(deserialize_with = "args::float")]
+    pub x: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub y: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub width: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    //wrap the content in a group with an SVG filter, which typst has to rasterize when exporting to PDF
+    #[default]
+    Filter,
+    //rewrite the colors themselves, keeping the result a pure vector graphic
+    Recolor,
+}
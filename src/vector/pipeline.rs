@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use xmltree::Element;
+
+use super::filters;
+use super::geometry;
+use super::recolor::{self, Color};
+use super::{add_svg_clip, add_svg_mask};
+use crate::args;
+use crate::error::GraynessError;
+use crate::limits::Limits;
+
+#[derive(Debug, Deserialize)]
+pub struct CropArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub x: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub y: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub width: f32,
+    #[serde(deserialize_with = "args::float")]
+    pub height: f32,
+    //additionally clip the content to the region, so nothing outside of it can show up
+    #[serde(default)]
+    pub clip: bool,
+}
+
+impl CropArgs {
+    //these end up in the width, height and viewBox attributes, which don't allow empty or invalid sizes
+    fn check(&self) -> Result<(), GraynessError> {
+        for (name, value) in [("x", self.x), ("y", self.y)] {
+            if !value.is_finite() {
+                return Err(GraynessError::invalid_argument(
+                    name,
+                    format!("expected a finite number, got {value}"),
+                ));
+            }
+        }
+        for (name, value) in [("width", self.width), ("height", self.height)] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(GraynessError::invalid_argument(
+                    name,
+                    format!("expected a positive number, got {value}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlurArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub sigma: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransparencyArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub alpha: f32,
+}
+
+//shared by brighten and huerotate
+#[derive(Debug, Deserialize)]
+pub struct AmountArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub amount: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MatrixArgs {
+    #[serde(deserialize_with = "args::float_matrix")]
+    pub matrix: [f32; 20],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaskArgs {
+    #[serde(with = "serde_bytes")]
+    pub mask: Vec<u8>,
+    #[serde(default)]
+    pub use_alpha: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateArgs {
+    #[serde(deserialize_with = "args::float")]
+    pub angle: f32,
+}
+
+//a single step of an SVG pipeline, encoded the same way as the raster operations
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Grayscale,
+    Crop(CropArgs),
+    Blur(BlurArgs),
+    Transparency(TransparencyArgs),
+    Invert,
+    Brighten(AmountArgs),
+    Huerotate(AmountArgs),
+    Matrix(MatrixArgs),
+    Mask(MaskArgs),
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Fliph,
+    Flipv,
+    Rotate(RotateArgs),
+}
+
+impl Operation {
+    //the filter primitive implementing this operation, None for operations which don't use a filter
+    pub fn primitive(&self) -> Option<Element> {
+        match self {
+            Operation::Grayscale => Some(filters::grayscale()),
+            Operation::Blur(a) => Some(filters::blur(a.sigma)),
+            Operation::Transparency(a) => Some(filters::transparency(a.alpha)),
+            Operation::Invert => Some(filters::invert()),
+            Operation::Brighten(a) => Some(filters::brighten(a.amount)),
+            Operation::Huerotate(a) => Some(filters::huerotate(a.amount)),
+            Operation::Matrix(a) => Some(filters::matrix(&a.matrix)),
+            _ => None,
+        }
+    }
+
+    //applies the color change of this operation to a single color, for the recolor mode
+    pub fn recolor(&self, c: Color) -> Color {
+        match self {
+            Operation::Grayscale => recolor::grayscale(c),
+            Operation::Transparency(a) => recolor::transparency(c, a.alpha),
+            Operation::Invert => recolor::invert(c),
+            Operation::Brighten(a) => recolor::brighten(c, a.amount),
+            Operation::Huerotate(a) => recolor::huerotate(c, a.amount),
+            Operation::Matrix(a) => recolor::matrix(c, &a.matrix),
+            _ => c,
+        }
+    }
+
+    //applies operations which change the geometry or structure of the SVG instead of using a filter
+    pub fn apply_structure(
+        &self,
+        svg_elem: &mut Element,
+        limits: &Limits,
+    ) -> Result<(), GraynessError> {
+        match self {
+            Operation::Mask(a) => add_svg_mask(svg_elem, &a.mask, a.use_alpha, limits)?,
+            Operation::Crop(a) => {
+                a.check()?;
+                geometry::crop(svg_elem, a.x, a.y, a.width, a.height);
+                if a.clip {
+                    add_svg_clip(svg_elem, a.x, a.y, a.width, a.height);
+                }
+            }
+            Operation::Rotate90 => geometry::rotate(svg_elem, 90.0)?,
+            Operation::Rotate180 => geometry::rotate(svg_elem, 180.0)?,
+            Operation::Rotate270 => geometry::rotate(svg_elem, 270.0)?,
+            Operation::Fliph => geometry::flip(svg_elem, true)?,
+            Operation::Flipv => geometry::flip(svg_elem, false)?,
+            Operation::Rotate(a) => {
+                if !a.angle.is_finite() {
+                    return Err(GraynessError::invalid_argument(
+                        "angle",
+                        format!("expected a finite number, got {}", a.angle),
+                    ));
+                }
+                geometry::rotate(svg_elem, a.angle)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
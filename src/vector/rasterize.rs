@@ -0,0 +1,145 @@
+use image::RgbaImage;
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use resvg::usvg::{Error, ImageHrefResolver, Options, Tree};
+use std::sync::{Mutex, PoisonError};
+
+use super::document::{check_limits, decompress, is_gzip};
+use crate::error::GraynessError;
+use crate::limits::Limits;
+
+//renders the SVG into an image of the given size, scaling it uniformly and centering it like `preserveAspectRatio="xMidYMid meet"`.
+//a width or height of 0 is derived from the other one, or from the size of the SVG if both are 0.
+//text is not rendered, since the plugin has no access to fonts, and text elements are skipped without an error
+pub fn rasterize(
+    svg_bytes: &[u8],
+    width: u32,
+    height: u32,
+    background: [u8; 4],
+    limits: &Limits,
+) -> Result<RgbaImage, GraynessError> {
+    //usvg would decompress SVGZ files and parse deeply nested elements recursively, without any limits
+    let decompressed;
+    let svg_bytes = if is_gzip(svg_bytes) {
+        decompressed = decompress(svg_bytes, limits)?;
+        decompressed.as_slice()
+    } else {
+        svg_bytes
+    };
+    check_limits(svg_bytes, limits)?;
+    let exceeded = Mutex::new(None);
+    let tree = Tree::from_data(svg_bytes, &options(limits, &exceeded)).map_err(parse_error)?;
+    if let Some(e) = exceeded
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+    {
+        return Err(e);
+    }
+    let size = tree.size();
+    let (svg_width, svg_height) = (size.width(), size.height());
+
+    //usvg only returns positive sizes, huge ones saturate and are rejected by the limits below
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let (width, height) = match (width, height) {
+        (0, 0) => (svg_width.ceil() as u32, svg_height.ceil() as u32),
+        (0, h) => ((h as f32 * svg_width / svg_height).round() as u32, h),
+        (w, 0) => (w, (w as f32 * svg_height / svg_width).round() as u32),
+        (w, h) => (w, h),
+    };
+    limits.check_size(width, height)?;
+    let mut pixmap = Pixmap::new(width, height).ok_or(GraynessError::InvalidSvg(format!(
+        "Could not create an image of {width}x{height} pixels"
+    )))?;
+
+    let [r, g, b, a] = background;
+    pixmap.fill(Color::from_rgba8(r, g, b, a));
+    //the size is within the limits, so rounding it to f32 at most shifts the SVG by a fraction of a pixel
+    #[allow(clippy::cast_precision_loss)]
+    let (width_f, height_f) = (width as f32, height as f32);
+    let scale = (width_f / svg_width).min(height_f / svg_height);
+    let transform = Transform::from_translate(
+        (width_f - svg_width * scale) / 2.0,
+        (height_f - svg_height * scale) / 2.0,
+    )
+    .pre_scale(scale, scale);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    //tiny-skia stores premultiplied colors
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(width, height, pixels).ok_or(GraynessError::EncodeFailed(
+        "Could not convert the rendered SVG to an image".to_string(),
+    ))
+}
+
+//usvg parses embedded SVGs recursively and resvg decodes embedded raster images when rendering, both without any limits.
+//so they are checked before, and the first one exceeding the limits is reported after parsing
+fn options<'a>(limits: &'a Limits, exceeded: &'a Mutex<Option<GraynessError>>) -> Options<'a> {
+    let resolve = ImageHrefResolver::default_data_resolver();
+    Options {
+        image_href_resolver: ImageHrefResolver {
+            resolve_data: Box::new(move |mime, data, options| {
+                if let Err(e) = check_embedded(&data, limits) {
+                    exceeded
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get_or_insert(e);
+                    return None;
+                }
+                resolve(mime, data, options)
+            }),
+            ..ImageHrefResolver::default()
+        },
+        ..Options::default()
+    }
+}
+
+//raster images are decoded to 8-bit RGBA, and anything else may be an SVG
+fn check_embedded(data: &[u8], limits: &Limits) -> Result<(), GraynessError> {
+    if let Ok(size) = imagesize::blob_size(data) {
+        let width = u32::try_from(size.width).unwrap_or(u32::MAX);
+        let height = u32::try_from(size.height).unwrap_or(u32::MAX);
+        return limits.check_size(width, height);
+    }
+    if is_gzip(data) {
+        //corrupt data is left to usvg, which skips the image like any other it can't load
+        return match decompress(data, limits) {
+            Ok(svg_bytes) => check_limits(&svg_bytes, limits),
+            Err(e @ GraynessError::LimitExceeded(_)) => Err(e),
+            Err(_) => Ok(()),
+        };
+    }
+    check_limits(data, limits)
+}
+
+//the message of roxmltree ends with the position, which is kept separately instead
+fn parse_error(e: Error) -> GraynessError {
+    match e {
+        Error::ParsingFailed(e) => {
+            let position = e.pos();
+            let message = e.to_string();
+            GraynessError::SvgParse {
+                line: position.row.into(),
+                col: position.col.into(),
+                message: message
+                    .strip_suffix(&format!(" at {position}"))
+                    .unwrap_or(&message)
+                    .to_string(),
+            }
+        }
+        //counted by usvg after resolving references, so it can be hit even within `max_elements`
+        Error::ElementsLimitReached => GraynessError::LimitExceeded(
+            "The SVG exceeds the limit of 1000000 elements of the renderer".to_string(),
+        ),
+        e => GraynessError::InvalidSvg(format!("Could not parse SVG data: {e}")),
+    }
+}
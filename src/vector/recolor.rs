@@ -0,0 +1,498 @@
+use xmltree::{Element, XMLNode};
+
+//rewrites the colors of an SVG directly instead of applying a filter, so the result stays a pure vector graphic
+
+//RGBA with all components from 0 to 1, the color channels in sRGB
+pub type Color = [f32; 4];
+
+//properties taking a color, `color` itself is included since it is the source of `currentColor`
+const COLOR_PROPERTIES: [&str; 6] = [
+    "fill",
+    "stroke",
+    "stop-color",
+    "flood-color",
+    "lighting-color",
+    "color",
+];
+
+//the filters default to color-interpolation-filters="linearRGB", so the color matrices are applied in linear RGB
+//to get the same result, see https://www.w3.org/TR/filter-effects-1/#attr-valuedef-color-interpolation-filters-linearrgb
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+//applies a 5x4 color matrix as feColorMatrix does, the offsets being given in the range from 0 to 1
+fn apply_matrix(c: Color, m: &[f32; 20]) -> Color {
+    let [r, g, b, a] = [
+        srgb_to_linear(c[0]),
+        srgb_to_linear(c[1]),
+        srgb_to_linear(c[2]),
+        c[3],
+    ];
+    let row = |i: usize| {
+        (m[i] * r + m[i + 1] * g + m[i + 2] * b + m[i + 3] * a + m[i + 4]).clamp(0.0, 1.0)
+    };
+    [
+        linear_to_srgb(row(0)),
+        linear_to_srgb(row(5)),
+        linear_to_srgb(row(10)),
+        row(15),
+    ]
+}
+
+//see https://www.w3.org/TR/filter-effects-1/#element-attrdef-fecolormatrix-values for the saturate and hueRotate matrices
+pub fn grayscale(c: Color) -> Color {
+    #[rustfmt::skip]
+    let m = [
+        0.213, 0.715, 0.072, 0.0, 0.0,
+        0.213, 0.715, 0.072, 0.0, 0.0,
+        0.213, 0.715, 0.072, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ];
+    apply_matrix(c, &m)
+}
+
+pub fn huerotate(c: Color, degrees: f32) -> Color {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    #[rustfmt::skip]
+    let m = [
+        0.213 + cos * 0.787 - sin * 0.213, 0.715 - cos * 0.715 - sin * 0.715, 0.072 - cos * 0.072 + sin * 0.928, 0.0, 0.0,
+        0.213 - cos * 0.213 + sin * 0.143, 0.715 + cos * 0.285 + sin * 0.140, 0.072 - cos * 0.072 - sin * 0.283, 0.0, 0.0,
+        0.213 - cos * 0.213 - sin * 0.787, 0.715 - cos * 0.715 + sin * 0.715, 0.072 + cos * 0.928 + sin * 0.072, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ];
+    apply_matrix(c, &m)
+}
+
+pub fn matrix(c: Color, m: &[f32; 20]) -> Color {
+    apply_matrix(c, m)
+}
+
+//invert and brighten are applied in sRGB, like their filter primitives
+pub fn invert(c: Color) -> Color {
+    [1.0 - c[0], 1.0 - c[1], 1.0 - c[2], c[3]]
+}
+
+pub fn brighten(c: Color, amount: f32) -> Color {
+    [
+        (c[0] + amount).clamp(0.0, 1.0),
+        (c[1] + amount).clamp(0.0, 1.0),
+        (c[2] + amount).clamp(0.0, 1.0),
+        c[3],
+    ]
+}
+
+pub fn transparency(c: Color, alpha: f32) -> Color {
+    [c[0], c[1], c[2], (c[3] * alpha).clamp(0.0, 1.0)]
+}
+
+//rewrites every color in attributes, inline styles and style sheets of the SVG
+pub fn recolor(svg_elem: &mut Element, f: &dyn Fn(Color) -> Color) {
+    //without an explicit fill or color, black is used, which has to be recolored as well.
+    //it is inserted as is, since the attributes of the root are recolored with all others
+    let black = "#000000";
+    for property in ["fill", "color"] {
+        let in_style = svg_elem.attributes.get("style").is_some_and(|style| {
+            declarations(style)
+                .filter_map(|declaration| declaration.split_once(':'))
+                .any(|(name, _)| name.trim() == property)
+        });
+        if !in_style && !svg_elem.attributes.contains_key(property) {
+            svg_elem
+                .attributes
+                .insert(property.to_string(), black.to_string());
+        }
+    }
+
+    recolor_element(svg_elem, f);
+}
+
+fn recolor_element(elem: &mut Element, f: &dyn Fn(Color) -> Color) {
+    for (name, value) in &mut elem.attributes {
+        if COLOR_PROPERTIES.contains(&name.as_str()) {
+            *value = recolor_value(value, f);
+        } else if name == "style" {
+            *value = recolor_css(value, f);
+        }
+    }
+
+    let is_style_sheet = elem.name == "style";
+    for child in &mut elem.children {
+        match child {
+            XMLNode::Element(child) => recolor_element(child, f),
+            XMLNode::Text(css) | XMLNode::CData(css) if is_style_sheet => {
+                *css = recolor_css(css, f);
+            }
+            _ => {}
+        }
+    }
+}
+
+//rewrites the color properties among the declarations of a style attribute or style sheet
+fn recolor_css(css: &str, f: &dyn Fn(Color) -> Color) -> String {
+    let mut res = String::with_capacity(css.len());
+    let mut rest = css;
+    loop {
+        let end = find_delimiter(rest, &['{', '}', ';']).unwrap_or(rest.len());
+        let (segment, tail) = rest.split_at(end);
+        //selectors may contain colons as well, but are followed by a block instead of ending a declaration
+        if tail.starts_with('{') {
+            res.push_str(segment);
+        } else {
+            res.push_str(&recolor_declaration(segment, f));
+        }
+
+        let Some(delimiter) = tail.chars().next() else {
+            return res;
+        };
+        res.push(delimiter);
+        rest = &tail[1..];
+    }
+}
+
+//the position of the first of the delimiters outside of parentheses and quotes,
+//which may contain semicolons as in `url(data:image/png;base64,...)`
+fn find_delimiter(css: &str, delimiters: &[char]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    for (i, c) in css.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, c) if depth == 0 && delimiters.contains(&c) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+//the declarations of a style attribute
+fn declarations(style: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(style);
+    std::iter::from_fn(move || {
+        let css = rest?;
+        match find_delimiter(css, &[';']) {
+            Some(end) => {
+                rest = Some(&css[end + 1..]);
+                Some(&css[..end])
+            }
+            None => rest.take(),
+        }
+    })
+}
+
+fn recolor_declaration(declaration: &str, f: &dyn Fn(Color) -> Color) -> String {
+    let Some((name, value)) = declaration.split_once(':') else {
+        return declaration.to_string();
+    };
+    if !COLOR_PROPERTIES.contains(&name.trim().to_lowercase().as_str()) {
+        return declaration.to_string();
+    }
+
+    //keep the surrounding whitespace
+    let trimmed = value.trim();
+    let leading = &value[..value.len() - value.trim_start().len()];
+    let trailing = &value[value.trim_end().len()..];
+    format!("{name}:{leading}{}{trailing}", recolor_value(trimmed, f))
+}
+
+//recolors a single value, leaving everything which isn't a plain color (like `none` or `currentColor`) untouched
+fn recolor_value(value: &str, f: &dyn Fn(Color) -> Color) -> String {
+    let value = value.trim();
+    if let Some(rest) = value.strip_suffix("!important") {
+        return format!("{} !important", recolor_value(rest, f));
+    }
+    //paint servers may be followed by a fallback color
+    if value.starts_with("url(")
+        && let Some(end) = value.find(')')
+    {
+        let (url, fallback) = value.split_at(end + 1);
+        if fallback.trim().is_empty() {
+            return value.to_string();
+        }
+        return format!("{url} {}", recolor_value(fallback, f));
+    }
+
+    match parse_color(value) {
+        Some(color) => format_color(f(color)),
+        None => value.to_string(),
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_color(c: Color) -> String {
+    let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let (r, g, b) = (channel(c[0]), channel(c[1]), channel(c[2]));
+    if c[3] >= 1.0 {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        let a = (c[3].clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+        format!("rgba({r}, {g}, {b}, {a})")
+    }
+}
+
+//parses the CSS color syntaxes which are allowed in SVG, see https://www.w3.org/TR/css-color-3/
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim().to_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    if let Some((function, args)) = value.strip_suffix(')').and_then(|v| v.split_once('(')) {
+        let args: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|a| !a.is_empty())
+            .collect();
+        return match function.trim() {
+            "rgb" | "rgba" => parse_rgb(&args),
+            "hsl" | "hsla" => parse_hsl(&args),
+            _ => None,
+        };
+    }
+    if value == "transparent" {
+        return Some([0.0, 0.0, 0.0, 0.0]);
+    }
+    let rgb = NAMED_COLORS
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, rgb)| *rgb)?;
+    let [_, r, g, b] = rgb.to_be_bytes();
+    Some([
+        f32::from(r) / 255.0,
+        f32::from(g) / 255.0,
+        f32::from(b) / 255.0,
+        1.0,
+    ])
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    let digit = |i: usize| u8::from_str_radix(hex.get(i..=i)?, 16).ok();
+    let pair = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    let bytes = match hex.len() {
+        3 | 4 => {
+            let mut bytes = [255; 4];
+            for (i, byte) in bytes.iter_mut().take(hex.len()).enumerate() {
+                *byte = digit(i)? * 17;
+            }
+            bytes
+        }
+        6 | 8 => {
+            let mut bytes = [255; 4];
+            for (i, byte) in bytes.iter_mut().take(hex.len() / 2).enumerate() {
+                *byte = pair(2 * i)?;
+            }
+            bytes
+        }
+        _ => return None,
+    };
+    Some(bytes.map(|b| f32::from(b) / 255.0))
+}
+
+//a number, or a percentage of the given maximum
+fn parse_number(value: &str, max: f32) -> Option<f32> {
+    match value.strip_suffix('%') {
+        Some(percentage) => Some(percentage.parse::<f32>().ok()? / 100.0 * max),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_alpha(args: &[&str]) -> Option<f32> {
+    match args.get(3) {
+        Some(alpha) => Some(parse_number(alpha, 1.0)?.clamp(0.0, 1.0)),
+        None => Some(1.0),
+    }
+}
+
+fn parse_rgb(args: &[&str]) -> Option<Color> {
+    if !(3..=4).contains(&args.len()) {
+        return None;
+    }
+    let channel = |i: usize| Some((parse_number(args[i], 255.0)? / 255.0).clamp(0.0, 1.0));
+    Some([channel(0)?, channel(1)?, channel(2)?, parse_alpha(args)?])
+}
+
+fn parse_hsl(args: &[&str]) -> Option<Color> {
+    if !(3..=4).contains(&args.len()) {
+        return None;
+    }
+    let hue = args[0]
+        .strip_suffix("deg")
+        .unwrap_or(args[0])
+        .parse::<f32>()
+        .ok()?;
+    let saturation = parse_number(args[1], 1.0)?.clamp(0.0, 1.0);
+    let lightness = parse_number(args[2], 1.0)?.clamp(0.0, 1.0);
+
+    //see https://www.w3.org/TR/css-color-3/#hsl-color
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let channel = |n: f32| {
+        let k = (n + hue / 30.0).rem_euclid(12.0);
+        lightness - chroma / 2.0 * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Some([channel(0.0), channel(8.0), channel(4.0), parse_alpha(args)?])
+}
+
+//see https://www.w3.org/TR/css-color-3/#svg-color
+const NAMED_COLORS: [(&str, u32); 147] = [
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("grey", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
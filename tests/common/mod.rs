@@ -0,0 +1,138 @@
+//a minimal wasm host calling the plugin like typst does, see https://typst.app/docs/reference/foundations/plugin/#protocol
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+use wasmi::{Caller, Engine, Extern, Instance, Linker, Module, Store, Val};
+
+#[derive(Default)]
+struct HostState {
+    args: Vec<u8>,
+    result: Option<Vec<u8>>,
+}
+
+pub struct Plugin {
+    store: Store<HostState>,
+    instance: Instance,
+}
+
+//the plugin is built once per test binary, unless `GRAYNESS_WASM` points to an already built plugin
+pub fn wasm_bytes() -> &'static [u8] {
+    static WASM: OnceLock<Vec<u8>> = OnceLock::new();
+    WASM.get_or_init(|| {
+        let path = match std::env::var_os("GRAYNESS_WASM") {
+            Some(path) => PathBuf::from(path),
+            None => build_plugin(),
+        };
+        std::fs::read(&path).unwrap_or_else(|e| panic!("Could not read {}: {e}", path.display()))
+    })
+}
+
+fn build_plugin() -> PathBuf {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    //a separate target directory, so the build doesn't wait for the lock held by `cargo test`
+    let target_dir = manifest_dir.join("target").join("plugin-test");
+    let cargo = std::env::var("CARGO").unwrap_or("cargo".to_string());
+    let status = Command::new(cargo)
+        .current_dir(&manifest_dir)
+        .args(["build", "--lib", "--profile", "plugin-test"])
+        .args(["--target", "wasm32-unknown-unknown", "--target-dir"])
+        .arg(&target_dir)
+        .status()
+        .expect("Could not run cargo to build the plugin");
+    assert!(
+        status.success(),
+        "Building the plugin failed, is the wasm32-unknown-unknown target installed?"
+    );
+    target_dir.join("wasm32-unknown-unknown/plugin-test/grayness.wasm")
+}
+
+pub fn module() -> Module {
+    Module::new(&Engine::default(), wasm_bytes()).expect("Could not load the plugin")
+}
+
+impl Plugin {
+    pub fn load() -> Plugin {
+        let module = module();
+        let mut store = Store::new(module.engine(), HostState::default());
+        let mut linker = <Linker<HostState>>::new(module.engine());
+        linker
+            .func_wrap(
+                "typst_env",
+                "wasm_minimal_protocol_write_args_to_buffer",
+                |mut caller: Caller<'_, HostState>, ptr: i32| {
+                    let memory = memory(&caller);
+                    let args = std::mem::take(&mut caller.data_mut().args);
+                    memory
+                        .write(&mut caller, ptr as u32 as usize, &args)
+                        .expect("The plugin passed an invalid argument buffer");
+                },
+            )
+            .unwrap();
+        linker
+            .func_wrap(
+                "typst_env",
+                "wasm_minimal_protocol_send_result_to_host",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                    let memory = memory(&caller);
+                    let mut result = vec![0; len as u32 as usize];
+                    memory
+                        .read(&caller, ptr as u32 as usize, &mut result)
+                        .expect("The plugin passed an invalid result buffer");
+                    caller.data_mut().result = Some(result);
+                },
+            )
+            .unwrap();
+        let instance = linker
+            .instantiate_and_start(&mut store, &module)
+            .expect("Could not instantiate the plugin");
+        Plugin { store, instance }
+    }
+
+    //like typst, the arguments are passed as their lengths and then copied into the buffer provided by the plugin,
+    //and a non-zero return value turns the result into an error message
+    pub fn call(&mut self, name: &str, args: &[&[u8]]) -> Result<Vec<u8>, String> {
+        let func = self
+            .instance
+            .get_func(&self.store, name)
+            .unwrap_or_else(|| panic!("The plugin has no function `{name}`"));
+        self.store.data_mut().args = args.concat();
+        let params: Vec<Val> = args.iter().map(|a| Val::I32(a.len() as i32)).collect();
+        let mut code = [Val::I32(0)];
+        func.call(&mut self.store, &params, &mut code)
+            .unwrap_or_else(|e| panic!("`{name}` panicked: {e}"));
+        let result = self
+            .store
+            .data_mut()
+            .result
+            .take()
+            .unwrap_or_else(|| panic!("`{name}` did not send a result"));
+        match code[0] {
+            Val::I32(0) => Ok(result),
+            _ => Err(String::from_utf8(result).expect("The error message is not valid UTF-8")),
+        }
+    }
+}
+
+fn memory(caller: &Caller<'_, HostState>) -> wasmi::Memory {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .expect("The plugin does not export its memory")
+}
+
+pub fn cbor<T: serde::Serialize>(value: &T) -> Vec<u8> {
+    minicbor_serde::to_vec(value).unwrap()
+}
+
+pub fn encode(img: &image::DynamicImage, format: image::ImageFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut bytes), format)
+        .unwrap();
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> image::DynamicImage {
+    image::load_from_memory(bytes).expect("The plugin returned an invalid image")
+}
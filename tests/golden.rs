@@ -0,0 +1,458 @@
+//compares the output of every plugin function on the fixtures in `tests/fixtures` with the golden files in `tests/golden`.
+//run `GRAYNESS_BLESS=1 cargo test --test golden` to regenerate them after an intended change, and review the diff of the images
+//they cover all formats, and the plugin they call is always built with the default features
+#![cfg(feature = "full")]
+
+mod common;
+
+use common::{Plugin, cbor};
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageReader, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+const RASTER_FIXTURES: &[&str] = &[
+    "alpha.png",
+    "deep.png",
+    "cmyk.jpg",
+    "animated.gif",
+    "palette.png",
+    "photo.tiff",
+    "hdr.exr",
+    "oriented.jpg",
+    "dpi.png",
+];
+const SVG_FIXTURES: &[&str] = &["layers.svg", "diagram.svg", "plot.svg"];
+
+//a pixel counts as different if one of its premultiplied channels differs by more than this,
+//which leaves room for rounding, different resampling and anti-aliasing between renderer versions
+const PIXEL_TOLERANCE: u8 = 24;
+//the share of pixels which may differ, and the limit for the mean difference of all channels
+const DIFFERENT_PIXELS: f64 = 0.005;
+const MEAN_DIFFERENCE: f64 = 1.0;
+
+const SEPIA: [f32; 20] = [
+    0.393, 0.769, 0.189, 0.0, 0.0, 0.349, 0.686, 0.168, 0.0, 0.0, 0.272, 0.534, 0.131, 0.0, 0.0,
+    0.0, 0.0, 0.0, 1.0, 0.0,
+];
+
+struct Case {
+    //the directory of the golden files, the function name unless a function is tested with several arguments
+    name: &'static str,
+    function: &'static str,
+    fixtures: &'static [&'static str],
+    args: Vec<Vec<u8>>,
+}
+
+fn case(function: &'static str, fixtures: &'static [&'static str], args: Vec<Vec<u8>>) -> Case {
+    Case {
+        name: function,
+        function,
+        fixtures,
+        args,
+    }
+}
+
+fn f(v: f32) -> Vec<u8> {
+    v.to_le_bytes().to_vec()
+}
+
+fn u(v: u32) -> Vec<u8> {
+    v.to_le_bytes().to_vec()
+}
+
+fn i(v: i32) -> Vec<u8> {
+    v.to_le_bytes().to_vec()
+}
+
+fn s(v: &str) -> Vec<u8> {
+    v.as_bytes().to_vec()
+}
+
+fn matrix() -> Vec<Vec<u8>> {
+    SEPIA.iter().map(|&v| f(v)).collect()
+}
+
+#[derive(Serialize)]
+struct MaskArgs {
+    #[serde(with = "serde_bytes")]
+    mask: Vec<u8>,
+    use_alpha: bool,
+}
+
+fn mask_args(use_alpha: bool) -> Vec<u8> {
+    let mask = fixture("palette.png");
+    cbor(&MaskArgs { mask, use_alpha })
+}
+
+fn raster_cases() -> Vec<Case> {
+    const ALPHA: &[&str] = &["alpha.png"];
+    let white = vec![255; 4];
+    vec![
+        case("grayscale", RASTER_FIXTURES, vec![]),
+        case("convert", RASTER_FIXTURES, vec![]),
+        case("convert_to", ALPHA, vec![s("jpeg")]),
+        case("auto_orient", ALPHA, vec![]),
+        case("decode", RASTER_FIXTURES, vec![]),
+        case(
+            "decode_cbor",
+            RASTER_FIXTURES,
+            vec![cbor(&json!({"auto_orient": false}))],
+        ),
+        case("infos", RASTER_FIXTURES, vec![]),
+        case("mask", ALPHA, vec![fixture("palette.png"), vec![0]]),
+        case("crop", ALPHA, vec![u(8), u(4), u(24), u(16)]),
+        case("blur", ALPHA, vec![f(1.5)]),
+        case("transparency", ALPHA, vec![vec![128]]),
+        case("invert", RASTER_FIXTURES, vec![]),
+        case("brighten", ALPHA, vec![i(40)]),
+        case("huerotate", ALPHA, vec![i(90)]),
+        case("matrix", ALPHA, matrix()),
+        case(
+            "resize",
+            ALPHA,
+            vec![u(96), u(40), s("catmullrom"), s("fit")],
+        ),
+        case("fit_to_dpi", ALPHA, vec![f(10.0), f(72.0)]),
+        case("rotate90", ALPHA, vec![]),
+        case("rotate180", ALPHA, vec![]),
+        case("rotate270", ALPHA, vec![]),
+        case("fliph", ALPHA, vec![]),
+        case("flipv", ALPHA, vec![]),
+        case("rotate", ALPHA, vec![f(30.0), white, s("bilinear")]),
+        case(
+            "pipeline",
+            ALPHA,
+            vec![cbor(
+                &json!([{"op": "grayscale"}, {"op": "blur", "sigma": 1}]),
+            )],
+        ),
+        case("trace", RASTER_FIXTURES, vec![cbor(&json!({"colors": 4}))]),
+        Case {
+            name: "trace_bw",
+            ..case("trace", ALPHA, vec![cbor(&json!({"mode": "bw"}))])
+        },
+        case(
+            "pipeline_cbor",
+            ALPHA,
+            vec![cbor(
+                &json!({"operations": [{"op": "invert"}], "format": "webp"}),
+            )],
+        ),
+        case("mask_cbor", ALPHA, vec![mask_args(false)]),
+        case(
+            "crop_cbor",
+            ALPHA,
+            vec![cbor(&json!({"x": 0, "y": 0, "width": 20, "height": 30}))],
+        ),
+        case("blur_cbor", ALPHA, vec![cbor(&json!({"sigma": 2}))]),
+        case(
+            "transparency_cbor",
+            ALPHA,
+            vec![cbor(&json!({"alpha": 100}))],
+        ),
+        case("brighten_cbor", ALPHA, vec![cbor(&json!({"amount": -40}))]),
+        case("huerotate_cbor", ALPHA, vec![cbor(&json!({"amount": 180}))]),
+        case("matrix_cbor", ALPHA, vec![cbor(&json!({"matrix": SEPIA}))]),
+        case(
+            "resize_cbor",
+            ALPHA,
+            vec![cbor(
+                &json!({"width": 24, "height": 24, "filter": "nearest", "mode": "fill"}),
+            )],
+        ),
+        case(
+            "fit_to_dpi_cbor",
+            ALPHA,
+            vec![cbor(&json!({"width_mm": 8, "dpi": 96}))],
+        ),
+        case(
+            "rotate_cbor",
+            ALPHA,
+            vec![cbor(&json!({"angle": -15, "interpolation": "nearest"}))],
+        ),
+    ]
+}
+
+fn svg_cases() -> Vec<Case> {
+    const LAYERS: &[&str] = &["layers.svg"];
+    let white = vec![255; 4];
+    vec![
+        case("svg_grayscale", SVG_FIXTURES, vec![]),
+        case("svg_mask", LAYERS, vec![fixture("alpha.png"), vec![1]]),
+        case("svg_crop", LAYERS, vec![f(10.0), f(5.0), f(30.0), f(20.0)]),
+        case("svg_blur", LAYERS, vec![f(1.0)]),
+        case("svg_transparency", LAYERS, vec![f(0.5)]),
+        case("svg_invert", SVG_FIXTURES, vec![]),
+        case("svg_brighten", LAYERS, vec![f(0.2)]),
+        case("svg_huerotate", LAYERS, vec![f(90.0)]),
+        case("svg_matrix", LAYERS, matrix()),
+        case("svg_rotate90", SVG_FIXTURES, vec![]),
+        case("svg_rotate180", LAYERS, vec![]),
+        case("svg_rotate270", LAYERS, vec![]),
+        case("svg_fliph", LAYERS, vec![]),
+        case("svg_flipv", LAYERS, vec![]),
+        case("svg_rotate", LAYERS, vec![f(30.0)]),
+        case(
+            "svg_pipeline",
+            LAYERS,
+            vec![cbor(&json!([{"op": "grayscale"}, {"op": "fliph"}]))],
+        ),
+        case("svg_rasterize", SVG_FIXTURES, vec![u(120), u(0), white]),
+        case(
+            "svg_pipeline_cbor",
+            SVG_FIXTURES,
+            vec![cbor(
+                &json!({"operations": [{"op": "grayscale"}], "mode": "recolor"}),
+            )],
+        ),
+        //the implicit black fill of the unfilled path is recolored as well
+        Case {
+            name: "svg_pipeline_cbor_unfilled",
+            ..case(
+                "svg_pipeline_cbor",
+                &["unfilled.svg"],
+                vec![cbor(
+                    &json!({"operations": [{"op": "invert"}], "mode": "recolor"}),
+                )],
+            )
+        },
+        case("svg_mask_cbor", LAYERS, vec![mask_args(false)]),
+        case(
+            "svg_crop_cbor",
+            LAYERS,
+            vec![cbor(
+                &json!({"x": 10, "y": 5, "width": 30, "height": 20, "clip": true}),
+            )],
+        ),
+        case("svg_blur_cbor", LAYERS, vec![cbor(&json!({"sigma": 2}))]),
+        case(
+            "svg_transparency_cbor",
+            LAYERS,
+            vec![cbor(&json!({"alpha": 0.3}))],
+        ),
+        case(
+            "svg_brighten_cbor",
+            LAYERS,
+            vec![cbor(&json!({"amount": 0.2, "mode": "recolor"}))],
+        ),
+        case(
+            "svg_huerotate_cbor",
+            LAYERS,
+            vec![cbor(&json!({"amount": 180}))],
+        ),
+        case(
+            "svg_matrix_cbor",
+            LAYERS,
+            vec![cbor(&json!({"matrix": SEPIA}))],
+        ),
+        case("svg_rotate_cbor", LAYERS, vec![cbor(&json!({"angle": 45}))]),
+        case(
+            "svg_rasterize_cbor",
+            SVG_FIXTURES,
+            vec![cbor(
+                &json!({"width": 100, "background": [255, 255, 255, 255], "format": "jpeg"}),
+            )],
+        ),
+    ]
+}
+
+fn detect_cases() -> Vec<Case> {
+    const ALL: &[&str] = &[
+        "alpha.png",
+        "deep.png",
+        "cmyk.jpg",
+        "animated.gif",
+        "palette.png",
+        "photo.tiff",
+        "hdr.exr",
+        "oriented.jpg",
+        "dpi.png",
+        "layers.svg",
+        "diagram.svg",
+        "plot.svg",
+    ];
+    let grayscale = cbor(&json!({"operations": [{"op": "grayscale"}]}));
+    vec![
+        case("detect", ALL, vec![]),
+        case(
+            "auto_pipeline",
+            &["alpha.png", "layers.svg"],
+            vec![grayscale],
+        ),
+    ]
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Could not read {}: {e}", path.display()))
+}
+
+#[derive(Deserialize)]
+struct Processed {
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+}
+
+//the result of a function in a form which can be compared with a golden file
+enum Output {
+    Image(RgbaImage),
+    Text(String),
+}
+
+impl Output {
+    fn extension(&self) -> &'static str {
+        match self {
+            Output::Image(_) => "png",
+            Output::Text(_) => "txt",
+        }
+    }
+}
+
+//images are decoded (only the first frame of animations), SVGs rendered with resvg and CBOR results written as JSON
+fn output(function: &str, input: &[u8], result: Result<Vec<u8>, String>) -> Output {
+    let bytes = match result {
+        Ok(bytes) => bytes,
+        Err(e) => return Output::Text(format!("error: {e}\n")),
+    };
+    if function == "decode" || function == "decode_cbor" {
+        //`decode` returns the pixels after applying the EXIF orientation, which may swap the size,
+        //while the `decode_cbor` case turns it off
+        let mut decoder = ImageReader::new(std::io::Cursor::new(input))
+            .with_guessed_format()
+            .unwrap()
+            .into_decoder()
+            .unwrap();
+        let orientation = decoder.orientation().unwrap();
+        let mut img = DynamicImage::from_decoder(decoder).unwrap();
+        if function == "decode" {
+            img.apply_orientation(orientation);
+        }
+        let (width, height) = img.dimensions();
+        return Output::Image(RgbaImage::from_raw(width, height, bytes).unwrap());
+    }
+    if function == "auto_pipeline" {
+        let processed: Processed = minicbor_serde::from_slice(&bytes).unwrap();
+        return output("", input, Ok(processed.data));
+    }
+    if let Ok(img) = image::load_from_memory(&bytes) {
+        return Output::Image(img.to_rgba8());
+    }
+    if bytes.starts_with(b"<?xml") || bytes.starts_with(b"<svg") {
+        let img = grayness::vector::rasterize::rasterize(&bytes, 0, 0, [0; 4], &Default::default())
+            .unwrap();
+        return Output::Image(img);
+    }
+    let value: Value = minicbor_serde::from_slice(&bytes).expect("Unknown output format");
+    Output::Text(serde_json::to_string_pretty(&value).unwrap() + "\n")
+}
+
+fn premultiplied(p: &image::Rgba<u8>) -> [i32; 4] {
+    let a = p[3] as i32;
+    [
+        p[0] as i32 * a / 255,
+        p[1] as i32 * a / 255,
+        p[2] as i32 * a / 255,
+        a,
+    ]
+}
+
+//None if the images match within the tolerance, otherwise a description of the difference
+fn compare(actual: &RgbaImage, expected: &RgbaImage) -> Option<String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Some(format!(
+            "size {:?} instead of {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+    let mut different = 0;
+    let mut total = 0;
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let (a, e) = (premultiplied(a), premultiplied(e));
+        let diffs = (0..4).map(|c| (a[c] - e[c]).unsigned_abs());
+        total += diffs.clone().sum::<u32>() as u64;
+        if diffs.max().unwrap() > PIXEL_TOLERANCE as u32 {
+            different += 1;
+        }
+    }
+    let pixels = (actual.width() * actual.height()).max(1) as f64;
+    let share = different as f64 / pixels;
+    let mean = total as f64 / pixels / 4.0;
+    (share > DIFFERENT_PIXELS || mean > MEAN_DIFFERENCE).then(|| {
+        format!(
+            "{:.2}% of the pixels differ, mean difference {mean:.2}",
+            share * 100.0
+        )
+    })
+}
+
+fn write(path: &Path, output: &Output) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    match output {
+        Output::Image(img) => img.save(path).unwrap(),
+        Output::Text(text) => std::fs::write(path, text).unwrap(),
+    }
+}
+
+fn run(cases: Vec<Case>) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let bless = std::env::var_os("GRAYNESS_BLESS").is_some();
+    let mut plugin = Plugin::load();
+    let mut failures = Vec::new();
+
+    for case in cases {
+        for name in case.fixtures {
+            let input = fixture(name);
+            let mut args: Vec<&[u8]> = vec![&input];
+            args.extend(case.args.iter().map(Vec::as_slice));
+            let actual = output(case.function, &input, plugin.call(case.function, &args));
+
+            let stem = Path::new(name).file_stem().unwrap().to_str().unwrap();
+            let file = PathBuf::from(case.name).join(format!("{stem}.{}", actual.extension()));
+            let golden = root.join("tests/golden").join(&file);
+            if bless {
+                write(&golden, &actual);
+                continue;
+            }
+
+            let difference = match (&actual, golden.exists()) {
+                (_, false) => Some("no golden file".to_string()),
+                (Output::Image(img), true) => {
+                    compare(img, &image::open(&golden).unwrap().to_rgba8())
+                }
+                (Output::Text(text), true) => (*text != std::fs::read_to_string(&golden).unwrap())
+                    .then(|| format!("got {text}")),
+            };
+            if let Some(difference) = difference {
+                //the actual output is kept next to the build artifacts for inspection
+                let actual_path = root.join("target/golden-actual").join(&file);
+                write(&actual_path, &actual);
+                failures.push(format!("{}: {difference}", file.display()));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} outputs differ from their golden files, see target/golden-actual and run with GRAYNESS_BLESS=1 to update them:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn raster_functions() {
+    run(raster_cases());
+}
+
+#[test]
+fn svg_functions() {
+    run(svg_cases());
+}
+
+#[test]
+fn detection() {
+    run(detect_cases());
+}
@@ -0,0 +1,487 @@
+//the tests cover all formats, and the plugin they call is always built with the default features
+#![cfg(feature = "full")]
+
+mod common;
+
+use base64::prelude::*;
+use common::{Plugin, cbor, decode, encode};
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use wasmi::{ExternType, ValType};
+
+const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10" viewBox="0 0 20 10"><rect width="20" height="10" fill="red"/></svg>"#;
+
+fn png(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+    let img = RgbaImage::from_pixel(width, height, Rgba(color));
+    encode(&DynamicImage::ImageRgba8(img), ImageFormat::Png)
+}
+
+fn svg_text(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).expect("The plugin returned invalid UTF-8")
+}
+
+//typst only accepts plugins whose functions take and return i32 values and which import nothing but the protocol functions
+#[test]
+fn exports_follow_the_protocol() {
+    let module = common::module();
+    for import in module.imports() {
+        assert_eq!(
+            import.module(),
+            "typst_env",
+            "unexpected import {}",
+            import.name()
+        );
+    }
+
+    let mut names = Vec::new();
+    for export in module.exports() {
+        if let ExternType::Func(func) = export.ty() {
+            assert!(
+                func.params().iter().all(|p| *p == ValType::I32),
+                "`{}` takes other arguments than lengths",
+                export.name()
+            );
+            assert_eq!(func.results(), [ValType::I32], "`{}`", export.name());
+            names.push(export.name());
+        }
+    }
+    for name in [
+        "grayscale",
+        "pipeline_cbor",
+        "svg_grayscale",
+        "svg_pipeline_cbor",
+        "detect",
+    ] {
+        assert!(names.contains(&name), "`{name}` is not exported");
+    }
+}
+
+#[test]
+fn raster_images_keep_their_size() {
+    let mut plugin = Plugin::load();
+    let img = decode(
+        &plugin
+            .call("grayscale", &[&png(4, 3, [255, 0, 0, 255])])
+            .unwrap(),
+    );
+    assert_eq!(img.dimensions(), (4, 3));
+    let [r, g, b, _] = img.get_pixel(0, 0).0;
+    assert!(r == g && g == b);
+}
+
+#[test]
+fn float_arguments() {
+    let mut plugin = Plugin::load();
+    let img = png(8, 8, [0, 0, 255, 255]);
+    assert!(plugin.call("blur", &[&img, &1.5f32.to_le_bytes()]).is_ok());
+
+    let err = plugin.call("blur", &[&img, &[0, 0, 0]]).unwrap_err();
+    assert_eq!(
+        err,
+        "[invalid-argument] Invalid argument `sigma`: expected 4 bytes for a float, got 3"
+    );
+
+    for sigma in [0.0, f32::NAN] {
+        let err = plugin
+            .call("blur", &[&img, &sigma.to_le_bytes()])
+            .unwrap_err();
+        assert!(
+            err.starts_with("[invalid-argument] Invalid argument `sigma`"),
+            "{err}"
+        );
+    }
+
+    let width = 10f32.to_le_bytes();
+    for dpi in [0.0, -72.0, f32::NAN, f32::INFINITY] {
+        let err = plugin
+            .call("fit_to_dpi", &[&img, &width, &dpi.to_le_bytes()])
+            .unwrap_err();
+        assert!(
+            err.starts_with("[invalid-argument] Invalid argument `dpi`"),
+            "{err}"
+        );
+    }
+}
+
+#[test]
+fn unsigned_arguments() {
+    let mut plugin = Plugin::load();
+    let args = [2u32, 1, 3, 2].map(u32::to_le_bytes);
+    let img = png(8, 8, [0, 0, 255, 255]);
+    let result = plugin
+        .call("crop", &[&img, &args[0], &args[1], &args[2], &args[3]])
+        .unwrap();
+    assert_eq!(decode(&result).dimensions(), (3, 2));
+
+    let args = [7u32, 0, 3, 2].map(u32::to_le_bytes);
+    let err = plugin
+        .call("crop", &[&img, &args[0], &args[1], &args[2], &args[3]])
+        .unwrap_err();
+    assert_eq!(
+        err,
+        "[invalid-argument] Invalid argument `width`: expected a value from 1 to 1, got 3"
+    );
+    let zero = 0u32.to_le_bytes();
+    let err = plugin
+        .call("resize", &[&img, &zero, &zero, b"nearest", b"exact"])
+        .unwrap_err();
+    assert!(
+        err.starts_with("[invalid-argument] Invalid argument `width`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn signed_arguments() {
+    let mut plugin = Plugin::load();
+    let img = png(2, 2, [100, 100, 100, 255]);
+    let result = plugin
+        .call("brighten", &[&img, &(-50i32).to_le_bytes()])
+        .unwrap();
+    assert_eq!(decode(&result).get_pixel(0, 0).0, [50, 50, 50, 255]);
+}
+
+#[test]
+fn color_and_string_arguments() {
+    let mut plugin = Plugin::load();
+    let img = png(10, 10, [0, 0, 0, 255]);
+    let angle = 45f32.to_le_bytes();
+    let result = plugin
+        .call("rotate", &[&img, &angle, &[255, 255, 255, 255], b"nearest"])
+        .unwrap();
+    assert_eq!(decode(&result).get_pixel(0, 0).0, [255, 255, 255, 255]);
+
+    let err = plugin
+        .call("rotate", &[&img, &angle, &[0, 0, 0, 0], b"cubic"])
+        .unwrap_err();
+    assert!(err.contains("`interpolation`"), "{err}");
+}
+
+#[derive(Serialize)]
+struct Op {
+    op: &'static str,
+}
+
+#[derive(Serialize)]
+struct Pipeline {
+    operations: Vec<Op>,
+    format: &'static str,
+}
+
+#[test]
+fn cbor_arguments() {
+    let mut plugin = Plugin::load();
+    let img = png(8, 8, [0, 0, 255, 255]);
+
+    //typst encodes whole numbers as integers
+    let sigma = cbor(&std::collections::BTreeMap::from([("sigma", 2)]));
+    assert!(plugin.call("blur_cbor", &[&img, &sigma]).is_ok());
+
+    let pipeline = Pipeline {
+        operations: vec![Op { op: "invert" }, Op { op: "rotate90" }],
+        format: "jpeg",
+    };
+    let result = plugin
+        .call("pipeline_cbor", &[&img, &cbor(&pipeline)])
+        .unwrap();
+    assert_eq!(image::guess_format(&result).unwrap(), ImageFormat::Jpeg);
+}
+
+#[test]
+fn svg_functions() {
+    let mut plugin = Plugin::load();
+    let result = svg_text(plugin.call("svg_grayscale", &[SVG]).unwrap());
+    assert!(result.contains("<filter"), "{result}");
+
+    let result = svg_text(plugin.call("svg_rotate90", &[SVG]).unwrap());
+    assert!(result.contains(r#"width="10""#), "{result}");
+    let relative = br#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" height="100%" viewBox="0 0 20 10"/>"#;
+    let result = svg_text(plugin.call("svg_rotate90", &[relative]).unwrap());
+    assert!(
+        result.contains(r#"width="100%""#) && result.contains(r#"height="100%""#),
+        "{result}"
+    );
+
+    //semicolons inside of URLs don't end a declaration
+    let styled = br#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"><rect width="1" height="1" style="fill:url('data:image/svg+xml;utf8,a;color:red') #ff0000;stroke:#000000"/></svg>"#;
+    let args = cbor(&serde_json::json!({ "operations": [{ "op": "invert" }], "mode": "recolor" }));
+    let result = svg_text(plugin.call("svg_pipeline_cbor", &[styled, &args]).unwrap());
+    assert!(
+        result.contains("utf8,a;color:red") && result.contains(") #00ffff;stroke:#ffffff"),
+        "{result}"
+    );
+
+    for (name, args) in [
+        ("width", [0.0, 0.0, 0.0, 5.0]),
+        ("height", [0.0, 0.0, 5.0, -5.0]),
+        ("x", [f32::NAN, 0.0, 5.0, 5.0]),
+    ] {
+        let args = args.map(f32::to_le_bytes);
+        let err = plugin
+            .call("svg_crop", &[SVG, &args[0], &args[1], &args[2], &args[3]])
+            .unwrap_err();
+        assert!(
+            err.starts_with(&format!("[invalid-argument] Invalid argument `{name}`")),
+            "{err}"
+        );
+    }
+    let err = plugin
+        .call("svg_rotate", &[SVG, &f32::NAN.to_le_bytes()])
+        .unwrap_err();
+    assert!(
+        err.starts_with("[invalid-argument] Invalid argument `angle`"),
+        "{err}"
+    );
+
+    let result = plugin.call("svg_rasterize", &[SVG, &[0; 4], &[0; 4], &[0; 4]]);
+    assert_eq!(decode(&result.unwrap()).get_pixel(0, 0).0, [255, 0, 0, 255]);
+}
+
+//each operation wraps the result of the previous ones, so the first is the innermost group
+#[test]
+fn svg_operations_keep_their_order() {
+    let mut plugin = Plugin::load();
+    let mut pipeline = |ops: serde_json::Value| {
+        let args = cbor(&serde_json::json!({ "operations": ops }));
+        svg_text(plugin.call("svg_pipeline_cbor", &[SVG, &args]).unwrap())
+    };
+    let position = |svg: &str, pattern: &str| {
+        svg.find(pattern)
+            .unwrap_or_else(|| panic!("{pattern} not found in {svg}"))
+    };
+
+    let result = pipeline(serde_json::json!([{ "op": "grayscale" }, { "op": "rotate90" }]));
+    assert!(
+        position(&result, "transform=") < position(&result, "filter="),
+        "{result}"
+    );
+    let result = pipeline(serde_json::json!([{ "op": "rotate90" }, { "op": "grayscale" }]));
+    assert!(
+        position(&result, "filter=") < position(&result, "transform="),
+        "{result}"
+    );
+
+    //the blur must not bleed past the edge of a later clip
+    let result = pipeline(serde_json::json!([
+        { "op": "blur", "sigma": 2 },
+        { "op": "crop", "x": 5, "y": 0, "width": 10, "height": 10, "clip": true },
+    ]));
+    assert!(
+        position(&result, "clip-path=") < position(&result, "filter="),
+        "{result}"
+    );
+}
+
+#[derive(Deserialize)]
+struct Detected {
+    kind: String,
+    format: Option<String>,
+}
+
+#[test]
+fn detection() {
+    let mut plugin = Plugin::load();
+    let detect = |plugin: &mut Plugin, bytes: &[u8]| -> Detected {
+        minicbor_serde::from_slice(&plugin.call("detect", &[bytes]).unwrap()).unwrap()
+    };
+    let detected = detect(&mut plugin, SVG);
+    assert_eq!(
+        (detected.kind.as_str(), detected.format.as_deref()),
+        ("svg", Some("svg"))
+    );
+    let detected = detect(&mut plugin, &png(1, 1, [0; 4]));
+    assert_eq!(
+        (detected.kind.as_str(), detected.format.as_deref()),
+        ("raster", Some("png"))
+    );
+
+    //license headers and doctypes can push the root element far from the start
+    let prolog = format!(
+        r#"<?xml version="1.0"?><!--{}--><!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">{}"#,
+        "license ".repeat(1000),
+        std::str::from_utf8(SVG).unwrap()
+    );
+    let detected = detect(&mut plugin, prolog.as_bytes());
+    assert_eq!(detected.kind, "svg");
+    let args = cbor(&serde_json::json!({ "operations": [{ "op": "grayscale" }] }));
+    assert!(
+        plugin
+            .call("auto_pipeline", &[prolog.as_bytes(), &args])
+            .is_ok()
+    );
+}
+
+#[test]
+fn errors_are_returned_as_messages() {
+    let mut plugin = Plugin::load();
+    //the code in brackets is stable, so typst wrappers can rely on it
+    let err = plugin.call("grayscale", &[b"not an image"]).unwrap_err();
+    assert_eq!(err, "[unsupported-format] Unknown image format");
+    let err = plugin.call("svg_grayscale", &[b"<svg"]).unwrap_err();
+    assert!(
+        err.starts_with("[svg-parse] Could not parse SVG data at line 1, column 5: "),
+        "{err}"
+    );
+    let err = plugin
+        .call("svg_grayscale", &[br#"<svg><g></svg>"#])
+        .unwrap_err();
+    assert!(err.starts_with("[svg-parse]"), "{err}");
+    let err = plugin.call("svg_rotate90", &[b"<svg/>"]).unwrap_err();
+    assert!(err.starts_with("[invalid-svg]"), "{err}");
+    let err = plugin
+        .call("svg_rasterize", &[b"<svg", &[0; 4], &[0; 4], &[0; 4]])
+        .unwrap_err();
+    assert!(err.starts_with("[svg-parse]"), "{err}");
+}
+
+//a valid PNG whose header declares the given size, so decoding it would allocate the whole image
+fn png_declaring(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = png(1, 1, [0; 4]);
+    //the IHDR chunk directly follows the 8-byte signature, its CRC covers the type and data
+    bytes[16..20].copy_from_slice(&width.to_be_bytes());
+    bytes[20..24].copy_from_slice(&height.to_be_bytes());
+    let mut crc = flate2::Crc::new();
+    crc.update(&bytes[12..29]);
+    bytes[29..33].copy_from_slice(&crc.sum().to_be_bytes());
+    bytes
+}
+
+#[derive(Serialize)]
+struct Limits {
+    max_memory: u32,
+}
+
+#[derive(Serialize)]
+struct LimitedPipeline {
+    operations: Vec<Op>,
+    limits: Limits,
+}
+
+#[test]
+fn limits() {
+    let mut plugin = Plugin::load();
+    let err = plugin
+        .call("grayscale", &[&png_declaring(100_000, 100_000)])
+        .unwrap_err();
+    assert!(err.starts_with("[limit-exceeded]"), "{err}");
+    assert!(err.contains("`max_width`"), "{err}");
+    let err = plugin
+        .call("grayscale", &[&png_declaring(10_000, 10_000)])
+        .unwrap_err();
+    assert!(err.contains("`max_memory`"), "{err}");
+
+    let args = cbor(&serde_json::json!({ "limits": { "max_width": 2 } }));
+    let err = plugin
+        .call("decode_cbor", &[&png(4, 4, [0; 4]), &args])
+        .unwrap_err();
+    assert!(err.contains("`max_width`"), "{err}");
+
+    let args = cbor(&serde_json::json!({ "limits": { "max_memory": 1 } }));
+    let err = plugin
+        .call("trace", &[&png(200, 200, [0, 0, 0, 255]), &args])
+        .unwrap_err();
+    assert!(err.starts_with("[limit-exceeded] Tracing"), "{err}");
+
+    let size = 20_000u32.to_le_bytes();
+    let err = plugin
+        .call(
+            "resize",
+            &[&png(2, 2, [0; 4]), &size, &size, b"nearest", b"exact"],
+        )
+        .unwrap_err();
+    assert!(err.contains("20000x20000"), "{err}");
+
+    //filling first scales the image to cover the whole area, here to 100x5000 pixels
+    let args = cbor(&serde_json::json!({
+        "width": 100, "height": 1, "mode": "fill", "limits": { "max_height": 50 }
+    }));
+    let err = plugin
+        .call("resize_cbor", &[&png(1, 50, [0; 4]), &args])
+        .unwrap_err();
+    assert!(err.contains("100x5000"), "{err}");
+    let args = cbor(&serde_json::json!({
+        "width": 100, "height": 1, "mode": "fill", "limits": { "max_memory": 1 }
+    }));
+    let err = plugin
+        .call("resize_cbor", &[&png(1, 50, [0; 4]), &args])
+        .unwrap_err();
+    assert!(err.contains("`max_memory`"), "{err}");
+
+    //rotating by 45° enlarges the image by up to √2
+    let args = cbor(
+        &serde_json::json!({ "angle": 45, "limits": { "max_width": 100, "max_height": 100 } }),
+    );
+    let err = plugin
+        .call("rotate_cbor", &[&png(100, 100, [0; 4]), &args])
+        .unwrap_err();
+    assert!(err.contains("141x141"), "{err}");
+
+    //deeply nested elements would overflow the stack of the recursive parsers
+    let nested = |depth: usize| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1">{}<rect width="1" height="1"/>{}</svg>"#,
+            "<g>".repeat(depth),
+            "</g>".repeat(depth)
+        )
+    };
+    let allowed = nested(120);
+    assert!(plugin.call("svg_grayscale", &[allowed.as_bytes()]).is_ok());
+    let result = plugin.call(
+        "svg_rasterize",
+        &[allowed.as_bytes(), &[0; 4], &[0; 4], &[0; 4]],
+    );
+    assert_eq!(decode(&result.unwrap()).get_pixel(0, 0).0, [0, 0, 0, 255]);
+    let nested = nested(1000);
+    let err = plugin
+        .call("svg_grayscale", &[nested.as_bytes()])
+        .unwrap_err();
+    assert!(err.contains("`max_depth`"), "{err}");
+    let err = plugin
+        .call(
+            "svg_rasterize",
+            &[nested.as_bytes(), &[0; 4], &[0; 4], &[0; 4]],
+        )
+        .unwrap_err();
+    assert!(err.contains("`max_depth`"), "{err}");
+
+    //images embedded as data URIs are checked before resvg parses or decodes them
+    let embedded = |uri: String| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"><image width="1" height="1" href="{uri}"/></svg>"#
+        )
+    };
+    let png_uri = format!(
+        "data:image/png;base64,{}",
+        BASE64_STANDARD.encode(png_declaring(100_000, 100_000))
+    );
+    let svg_uri = format!(
+        "data:image/svg+xml;base64,{}",
+        BASE64_STANDARD.encode(&nested)
+    );
+    for (uri, limit) in [(png_uri, "`max_width`"), (svg_uri, "`max_depth`")] {
+        let err = plugin
+            .call(
+                "svg_rasterize",
+                &[embedded(uri).as_bytes(), &[0; 4], &[0; 4], &[0; 4]],
+            )
+            .unwrap_err();
+        assert!(err.contains(limit), "{err}");
+    }
+
+    //2 MiB of padding compress to a few KiB
+    let padded = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><!--{}--></svg>"#,
+        " ".repeat(2 << 20)
+    );
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    std::io::Write::write_all(&mut encoder, padded.as_bytes()).unwrap();
+    let svgz = encoder.finish().unwrap();
+    let args = LimitedPipeline {
+        operations: vec![Op { op: "grayscale" }],
+        limits: Limits { max_memory: 1 },
+    };
+    for function in ["svg_pipeline_cbor", "auto_pipeline"] {
+        let err = plugin.call(function, &[&svgz, &cbor(&args)]).unwrap_err();
+        assert!(err.contains("SVGZ"), "{err}");
+    }
+}